@@ -0,0 +1,40 @@
+use std::hint::black_box;
+use std::time::Instant;
+use tiny_fl::Parser;
+
+/// Number of functions in the synthetic input.
+const FUNCTIONS : usize = 2000;
+
+/// Number of times each benchmark is repeated.
+const ITERATIONS : usize = 5;
+
+/// Generate a large synthetic input consisting of many functions,
+/// each containing a mixture of statements and expressions.
+fn generate(n: usize) -> String {
+    let mut out = String::new();
+    for i in 0..n {
+        out.push_str(&format!("// Function {i}\n"));
+        out.push_str(&format!("function f{i}(uint[] xs, uint x, (uint,bool) t) -> (uint r)\n"));
+        out.push_str("requires x < |xs|\n");
+        out.push_str("ensures r >= 0\n{\n");
+        out.push_str("  assert (x + 1) > x;\n");
+        out.push_str("  assume t.0 == xs[x];\n");
+        out.push_str(&format!("  if xs[x] == {i} {{ x }} else {{ /* recurse */ f{i}(xs, x, t) }}\n"));
+        out.push_str("}\n\n");
+    }
+    out
+}
+
+fn main() {
+    let input = generate(FUNCTIONS);
+    println!("input: {} functions, {} characters",FUNCTIONS,input.len());
+    //
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let mut parser = Parser::new(black_box(&input));
+        let terms = parser.parse().unwrap();
+        let elapsed = start.elapsed();
+        assert_eq!(terms.len(),FUNCTIONS);
+        println!("parse: {:?} ({} terms)",elapsed,parser.heap.len());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::Span;
+
 // ===================================================================
 // Binary Operators
 // ===================================================================
@@ -19,10 +21,23 @@ pub enum BinOp {
     GreaterThanOrEquals,
     // Logical
     LogicalAnd,
+    LogicalIff,
     LogicalImplies,
     LogicalOr
 }
 
+// ===================================================================
+// Unary Operators
+// ===================================================================
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum UnaryOp {
+    // Arithmetic
+    Negate,
+    // Logical
+    LogicalNot
+}
+
 // ===================================================================
 // Term
 // ===================================================================
@@ -35,6 +50,9 @@ pub enum Term {
     Assert(usize),
     Assume(usize),
     Block(Vec<usize>),
+    Let(String,usize),
+    /// Destructure a tuple into its components (e.g. `let (x,y) = e`)
+    LetTuple(Vec<String>,usize),
     // Expressions
     ArrayAccess{src: usize, index: usize},
     ArrayGenerator(usize,usize),
@@ -50,6 +68,7 @@ pub enum Term {
     StaticInvoke(String,Vec<usize>),
     TupleAccess(usize,usize),
     TupleConstructor(Vec<usize>),
+    Unary(UnaryOp,usize),
     // Types
     ArrayType(usize),
     BoolType,
@@ -57,6 +76,33 @@ pub enum Term {
     TupleType(Vec<usize>)
 }
 
+impl Term {
+    /// Get the heap indices of the immediate subterms of this term.
+    pub fn children(&self) -> Vec<usize> {
+        match self {
+            Term::Function(f) => {
+                let mut r : Vec<usize> = f.params.iter().chain(&f.rets).map(|p| p.0).collect();
+                r.extend(&f.requires);
+                r.extend(&f.ensures);
+                r.push(f.body);
+                r
+            }
+            Term::Assert(e)|Term::Assume(e)|Term::Let(_,e)|Term::LetTuple(_,e) => vec![*e],
+            Term::ArrayLength(e)|Term::Braced(e)|Term::TupleAccess(e,_)|Term::Unary(_,e) => vec![*e],
+            Term::ArrayType(e) => vec![*e],
+            Term::ArrayAccess{src,index} => vec![*src,*index],
+            Term::ArrayGenerator(item,len) => vec![*item,*len],
+            Term::ArraySlice{src,start,end} => vec![*src,*start,*end],
+            Term::Binary(_,lhs,rhs) => vec![*lhs,*rhs],
+            Term::IfElse{cond,tt,ff} => vec![*cond,*tt,*ff],
+            Term::Block(ts)|Term::ArrayConstructor(ts)|Term::StaticInvoke(_,ts) => ts.clone(),
+            Term::TupleConstructor(ts)|Term::TupleType(ts) => ts.clone(),
+            Term::BoolLiteral(_)|Term::IntLiteral(_)|Term::VarAccess(_) => Vec::new(),
+            Term::BoolType|Term::IntType(_) => Vec::new()
+        }
+    }
+}
+
 // ===================================================================
 // Function
 // ===================================================================
@@ -75,28 +121,43 @@ pub struct Function {
 // SyntacticHeap
 // ===================================================================
 
-/// Simplest possible implementation of a syntactic heap.
+/// Simplest possible implementation of a syntactic heap.  Alongside
+/// each term is recorded the span of source characters from which it
+/// was parsed, allowing later passes to report locations.
 pub struct SyntacticHeap{
-    nodes: Vec<Term>
+    nodes: Vec<Term>,
+    spans: Vec<Span>
 }
 
 impl SyntacticHeap {
     pub fn new() -> Self {
-        SyntacticHeap{nodes: Vec::new()}
+        SyntacticHeap{nodes: Vec::new(), spans: Vec::new()}
     }
 
     pub fn len(&self) -> usize {
         self.nodes.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
     pub fn get(&self, index: usize) -> &Term {
         &self.nodes[index]
     }
 
-    /// Allocate a new term into this heap
-    pub fn alloc(&mut self, term: Term) -> usize {
+    /// Get the span of source characters for the term at a given
+    /// index.
+    pub fn span(&self, index: usize) -> Span {
+        self.spans[index]
+    }
+
+    /// Allocate a new term into this heap, along with the span of
+    /// source characters it corresponds to.
+    pub fn alloc(&mut self, term: Term, span: Span) -> usize {
         let index = self.len();
         self.nodes.push(term);
+        self.spans.push(span);
         index
     }
 
@@ -105,6 +166,12 @@ impl SyntacticHeap {
     }
 }
 
+impl Default for SyntacticHeap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // ===================================================================
 // SyntacticRef
 // ===================================================================
@@ -113,3 +180,16 @@ pub struct SyntacticRef<'a> {
     pub heap: &'a SyntacticHeap,
     pub index: usize
 }
+
+impl<'a> SyntacticRef<'a> {
+    /// Get the term this reference refers to.
+    pub fn term(&self) -> &'a Term {
+        self.heap.get(self.index)
+    }
+
+    /// Get the span of source characters for the term this reference
+    /// refers to.
+    pub fn span(&self) -> Span {
+        self.heap.span(self.index)
+    }
+}
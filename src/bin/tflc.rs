@@ -1,11 +1,11 @@
 use std::error::Error;
 use std::{fs};
 use std::ffi::OsString;
-use std::path::PathBuf;
 use clap::{arg, Arg, ArgMatches, Command, value_parser};
+use clap::builder::PossibleValuesParser;
 //
-use tiny_fl::{Parser,RustPrinter,SyntacticHeap,Verifier};
-use tiny_fl::circuit::{Circuit,Outcome,SmtLibCircuit,SmtSolver};
+use tiny_fl::{Definitions,Diagnostic,Parser,RustPrinter,SourceMap,SyntacticHeap,TypeChecker,Typing,Verifier,unchecked_recursion};
+use tiny_fl::circuit::{Circuit,Limits,Outcome,SmtLibCircuit,SmtSolver,SolverProfile};
 
 fn main() -> Result<(), Box<dyn Error>> {
     // Parse command-line arguments
@@ -24,7 +24,12 @@ fn main() -> Result<(), Box<dyn Error>> {
             Command::new("verify")
                 .about("Verify a given source file")
                 .arg(Arg::new("z3-static").long("z3-static"))
-                .arg(Arg::new("solver-path").long("solver-path").default_value("z3").value_parser(value_parser!(OsString)))
+                .arg(Arg::new("solver").long("solver").takes_value(true).default_value("z3").value_parser(PossibleValuesParser::new(SolverProfile::NAMES)))
+                .arg(Arg::new("timeout").long("timeout").takes_value(true).value_name("ms").value_parser(value_parser!(u64)))
+                .arg(Arg::new("rlimit").long("rlimit").takes_value(true).value_parser(value_parser!(u64)))
+                .arg(Arg::new("solver-path").long("solver-path").takes_value(true).value_parser(value_parser!(OsString)))
+                .arg(arg!(--definitions "Expose function bodies to callers"))
+                .arg(Arg::new("fuel").long("fuel").takes_value(true).default_value("5").value_parser(value_parser!(usize)))
                 .arg(Arg::new("file").required(true))
                 .visible_alias("v")
         )
@@ -46,16 +51,24 @@ fn compile(args: &ArgMatches) -> Result<bool, Box<dyn Error>> {
     let filename = args.get_one::<String>("file").unwrap();
     // Read file
     let contents = fs::read_to_string(filename)?;
+    let source = SourceMap::new(&contents);
     let mut parser = Parser::new(&contents);
     // Parse file
     let terms = match parser.parse() {
         Ok(terms) => terms,
-        Err(_) => {
-            panic!("failed parsing: {filename}");
+        Err(e) => {
+            let d = Diagnostic::error(&e.message,Some(e.span()));
+            eprint!("{}",d.render(filename,&source));
+            return Ok(false);
         }
     };
+    // Type check file
+    let typing = match typecheck(filename,&source,&parser.heap,&terms) {
+        Some(typing) => typing,
+        None => { return Ok(false); }
+    };
     // Write file
-    let mut rp = RustPrinter::new(&parser.heap);
+    let mut rp = RustPrinter::new(&parser.heap,&typing);
     //
     for t in terms {
         rp.generate(t);
@@ -71,55 +84,123 @@ fn verify(args: &ArgMatches) -> Result<bool, Box<dyn Error>> {
     let filename = args.get_one::<String>("file").unwrap();
     // Check whether to use Z3 directly
     let z3_static = args.contains_id("z3-static");
-    let solver_path = args.get_one::<OsString>("solver-path").unwrap();
+    let profile = SolverProfile::from_name(args.get_one::<String>("solver").unwrap()).unwrap();
+    // Path to solver (which overrides that of the profile)
+    let solver_path = match (args.get_one::<OsString>("solver-path"),profile.executable()) {
+        (Some(path),_) => path.clone(),
+        (None,Some(exe)) => OsString::from(exe),
+        (None,None) => {
+            return Err(format!("solver `{profile}` requires --solver-path").into());
+        }
+    };
+    // Limits on each check
+    let limits = Limits{
+        timeout: args.get_one::<u64>("timeout").copied(),
+        rlimit: args.get_one::<u64>("rlimit").copied()
+    };
+    // Expose function bodies using recursive definitions where
+    // supported, falling back to (bounded) unrolling otherwise.
+    let definitions = match (args.contains_id("definitions"),z3_static || profile.supports_recursion()) {
+        (false,_) => Definitions::Opaque,
+        (true,true) => Definitions::Recursive,
+        (true,false) => Definitions::Unroll(*args.get_one::<usize>("fuel").unwrap())
+    };
     // Read file
     let contents = fs::read_to_string(filename)?;
+    let source = SourceMap::new(&contents);
     let mut parser = Parser::new(&contents);
     // Parse input
     let terms = match parser.parse() {
         Ok(terms) => terms,
-        Err(_) => {
-            panic!("failed parsing: {filename}");
+        Err(e) => {
+            let d = Diagnostic::error(&e.message,Some(e.span()));
+            eprint!("{}",d.render(filename,&source));
+            return Ok(false);
         }
     };
+    // Type check input
+    let typing = match typecheck(filename,&source,&parser.heap,&terms) {
+        Some(typing) => typing,
+        None => { return Ok(false); }
+    };
+    // Warn about functions whose bodies cannot be exposed
+    if definitions != Definitions::Opaque {
+        for index in unchecked_recursion(&parser.heap,&terms) {
+            let d = Diagnostic::warning("function body not exposed to callers",Some(parser.heap.span(index)))
+                .with_note("recursion could not be shown to terminate");
+            eprint!("{}",d.render(filename,&source));
+        }
+    }
     // Construct verifier and generate circuit
     if z3_static {
         // Statically linked Z3 has been requested.
-        z3_check(&parser.heap,&terms)
+        z3_check(filename,&source,&parser.heap,&typing,&terms,definitions,limits)
     } else {
         // Construcnt SmtSolver instance
-        let solver = SmtSolver::new(solver_path.as_ref());
+        let solver = SmtSolver::new(profile,solver_path.as_ref()).with_limits(limits);
         // Construct SmtLib circuit
         let smtlib = SmtLibCircuit::new(solver);
         // Do it!
-        check(&parser.heap,&terms,smtlib)
+        check(filename,&source,&parser.heap,&typing,&terms,definitions,smtlib)
     }
 }
 
-fn check<C:Circuit>(heap: &SyntacticHeap, terms: &[usize], circuit: C) -> Result<bool, Box<dyn Error>> {
+/// Type check a given set of declarations, reporting any error
+/// encountered.
+fn typecheck(filename: &str, source: &SourceMap, heap: &SyntacticHeap, terms: &[usize]) -> Option<Typing> {
+    match TypeChecker::new(heap).check(terms) {
+        Ok(typing) => Some(typing),
+        Err(e) => {
+            let d = Diagnostic::error(&e.message,Some(heap.span(e.index)));
+            eprint!("{}",d.render(filename,source));
+            None
+        }
+    }
+}
+
+fn check<C:Circuit>(filename: &str, source: &SourceMap, heap: &SyntacticHeap, typing: &Typing, terms: &[usize], definitions: Definitions, circuit: C) -> Result<bool, Box<dyn Error>> {
     //
-    let circuit = Verifier::new(heap,circuit).to_circuit(&terms)?;
+    let circuit = Verifier::new(heap,typing,circuit).with_definitions(definitions).to_circuit(terms)?;
     let mut checks = 0;
     let mut errors = 0;
     let mut warnings = 0;
+    let mut timeouts = 0;
 
     // Check conditions holds
-    for outcome in circuit.check() {
+    for (vc,outcome) in circuit.check()? {
+        let span = Some(heap.span(vc.index));
         match outcome {
             Outcome::Valid => { }
             Outcome::Unknown => {
                 warnings += 1;
-                println!("Warning");
+                let d = Diagnostic::warning(&vc.kind.to_string(),span)
+                    .with_note("solver could not establish this condition");
+                eprint!("{}",d.render(filename,source));
             }
-            Outcome::Invalid => {
-                println!("Error");
+            Outcome::Timeout => {
+                warnings += 1;
+                timeouts += 1;
+                let d = Diagnostic::warning(&vc.kind.to_string(),span)
+                    .with_note("solver exceeded its time or resource limit");
+                eprint!("{}",d.render(filename,source));
+            }
+            Outcome::Invalid(model) => {
+                let mut d = Diagnostic::error(&vc.kind.to_string(),span);
+                if !model.is_empty() {
+                    d = d.with_note(&format!("counterexample: {model}"));
+                }
+                eprint!("{}",d.render(filename,source));
                 errors += 1;
             }
         }
         checks += 1;
     }
-    println!("Verified {} check(s): {} errors / {} warnings",checks,errors,warnings);
-    Ok(true)
+    print!("Verified {} check(s): {} errors / {} warnings",checks,errors,warnings);
+    if timeouts > 0 {
+        print!(" ({} timed out)",timeouts);
+    }
+    println!();
+    Ok(errors == 0)
 }
 
 // ===================================================================
@@ -127,14 +208,14 @@ fn check<C:Circuit>(heap: &SyntacticHeap, terms: &[usize], circuit: C) -> Result
 // ===================================================================
 
 #[cfg(feature="z3-static")]
-fn z3_check(heap: &SyntacticHeap, terms: &[usize]) -> Result<bool, Box<dyn Error>> {
+fn z3_check(filename: &str, source: &SourceMap, heap: &SyntacticHeap, typing: &Typing, terms: &[usize], definitions: Definitions, limits: Limits) -> Result<bool, Box<dyn Error>> {
     let cfg = z3::Config::new();
     let context = z3::Context::new(&cfg);
-    let z3 = tiny_fl::circuit::Z3Circuit::new(&context);
-    check(heap,&terms,z3)
+    let z3 = tiny_fl::circuit::Z3Circuit::new(&context).with_limits(limits);
+    check(filename,source,heap,typing,terms,definitions,z3)
 }
 
 #[cfg(not(feature="z3-static"))]
-fn z3_check(heap: &SyntacticHeap, terms: &[usize]) -> Result<bool, Box<dyn Error>> {
+fn z3_check(_filename: &str, _source: &SourceMap, _heap: &SyntacticHeap, _typing: &Typing, _terms: &[usize], _definitions: Definitions, _limits: Limits) -> Result<bool, Box<dyn Error>> {
     panic!("Z3 was not statically linked!")
 }
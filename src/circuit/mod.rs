@@ -1,5 +1,8 @@
 mod smtlib;
 
+use std::fmt;
+use std::error::Error;
+
 pub use smtlib::*;
 
 #[cfg(feature="z3-static")]
@@ -11,10 +14,127 @@ pub enum Outcome {
     /// Indicates a given condition holds for all interpretations.
     Valid,
     /// Indicates a given condition does not hold for all
-    /// interpretations (ideally it would include a counter-example).
-    Invalid,
-    /// Unknown outcome (e.g. becaues of a timeout).
-    Unknown
+    /// interpretations, along with a counter-example.
+    Invalid(Model),
+    /// Unknown outcome (e.g. because the problem is beyond the
+    /// solver).
+    Unknown,
+    /// Unknown outcome because the solver exceeded its time (or
+    /// resource) limit.
+    Timeout
+}
+
+/// Limits placed on the solver when checking each condition.  By
+/// default, there are none.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct Limits {
+    /// Maximum time (in milliseconds) to spend on a condition.
+    pub timeout: Option<u64>,
+    /// Maximum resources to spend on a condition, in solver-specific
+    /// units.
+    pub rlimit: Option<u64>
+}
+
+impl Limits {
+    pub fn is_none(&self) -> bool {
+        self.timeout.is_none() && self.rlimit.is_none()
+    }
+}
+
+/// Determine whether the reason given by a solver for an unknown
+/// outcome indicates a limit was exceeded (e.g. `timeout`, or
+/// `resourceout`).  Solvers differ here, so this is a best effort.
+fn limit_exceeded(reason: &str) -> bool {
+    reason.contains("timeout") || reason.contains("resource") || reason.contains("canceled")
+}
+
+/// A counter-example to some condition, which assigns a value to each
+/// variable involved.  Variables are identified by their source-level
+/// names.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Model {
+    assignments: Vec<(String,String)>
+}
+
+impl Model {
+    pub fn new() -> Self {
+        Self{assignments: Vec::new()}
+    }
+
+    /// Assign a given value to a given variable.
+    pub fn assign(&mut self, var: &str, value: &str) {
+        self.assignments.push((var.to_string(),value.to_string()));
+    }
+
+    /// Get the value assigned to a given variable (if any).
+    pub fn get(&self, var: &str) -> Option<&str> {
+        self.assignments.iter().find(|(v,_)| v == var).map(|(_,val)| val.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assignments.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&(String,String)> {
+        self.assignments.iter()
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i,(var,val)) in self.assignments.iter().enumerate() {
+            if i != 0 { write!(f,", ")?; }
+            write!(f,"{var} = {val}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Identifies the kind of a verification condition, and hence the
+/// source construct which gave rise to it.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum VcKind {
+    /// An explicit `assert` statement.
+    Assert,
+    /// A postcondition of the enclosing function.
+    Ensures,
+    /// A precondition of some function at a call site.
+    Requires,
+    /// The divisor of a division (or remainder) is non-zero.
+    DivByZero,
+    /// An unsigned subtraction (or negation) does not go negative.
+    Underflow,
+    /// An array access is within bounds.
+    Bounds
+}
+
+impl fmt::Display for VcKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            VcKind::Assert => "assertion may not hold",
+            VcKind::Ensures => "postcondition may not hold",
+            VcKind::Requires => "precondition may not hold",
+            VcKind::DivByZero => "possible division by zero",
+            VcKind::Underflow => "possible integer underflow",
+            VcKind::Bounds => "possible out-of-bounds access"
+        };
+        write!(f,"{s}")
+    }
+}
+
+/// Describes a verification condition, such that any failure can be
+/// reported against the source construct which gave rise to it.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct VcDescriptor {
+    pub kind: VcKind,
+    /// Heap index of the originating term.
+    pub index: usize
+}
+
+impl VcDescriptor {
+    pub fn new(kind: VcKind, index: usize) -> Self {
+        Self{kind,index}
+    }
 }
 
 /// A circuit represents an encoding of information using logical or
@@ -27,6 +147,8 @@ pub trait Circuit {
     type Bool : Bool<Any=Self::Term>;
     type Int : Int<Any=Self::Term,Bool=Self::Bool>;
     type Function : Function<Any=Self::Term>;
+    type Array : Array<Any=Self::Term,Int=Self::Int>;
+    type Tuple : Tuple<Any=Self::Term>;
 
     /// Declare a boolean variable
     fn declare_bool(&mut self, name: &str) -> Self::Bool;
@@ -34,13 +156,31 @@ pub trait Circuit {
     /// Declare an integer variable
     fn declare_int(&mut self, name: &str) -> Self::Int;
 
-    /// Declare an (uninterpreted) function.
+    /// Declare an array variable whose elements have a given type.
+    fn declare_array(&mut self, name: &str, element: &Self::Type) -> Self::Array;
+
+    /// Declare a tuple variable whose fields have the given types.
+    fn declare_tuple(&mut self, name: &str, fields: &[Self::Type]) -> Self::Tuple;
+
+    /// Declare an (uninterpreted) function.  A function with multiple
+    /// returns produces a tuple of them.
     fn declare_fn(&mut self, name: &str, params: &[Self::Type], returns: &[Self::Type]) -> Self::Function;
 
+    /// Construct a parameter for use in a function definition.
+    /// Unlike a declared variable, this is bound by the definition.
+    fn declare_param(&mut self, name: &str, typ: &Self::Type) -> Self::Term;
+
+    /// Define a previously declared function in terms of its
+    /// parameters (constructed using `declare_param()`) and body.
+    /// The body may invoke any declared function, including this one.
+    fn define_fn(&mut self, function: &Self::Function, params: &[(Self::Term,Self::Type)], body: &Self::Term);
+
     /// Construct a boolean term from a boolean value.
+    #[allow(clippy::wrong_self_convention)]
     fn from_bool(&self, val: bool) -> Self::Bool;
 
     /// Construct a boolean term from a boolean value.
+    #[allow(clippy::wrong_self_convention)]
     fn from_usize(&self, val: usize) -> Self::Int;
 
     /// Construct a boolean type
@@ -49,13 +189,36 @@ pub trait Circuit {
     /// Construct an integer type
     fn int_type(&self) -> Self::Type;
 
+    /// Construct an array type with elements of a given type.
+    fn array_type(&self, element: &Self::Type) -> Self::Type;
+
+    /// Construct a tuple type with fields of the given types.  This
+    /// may introduce a declaration into the circuit.
+    fn tuple_type(&mut self, fields: &[Self::Type]) -> Self::Type;
+
+    /// Construct a tuple holding exactly the given items, which have
+    /// the given types.
+    fn tuple_from(&mut self, fields: &[Self::Type], items: &[Self::Term]) -> Self::Tuple;
+
+    /// Construct an array holding exactly the given items, which
+    /// have the given type.
+    fn array_from(&self, element: &Self::Type, items: &[Self::Term]) -> Self::Array;
+
+    /// Construct an array of a given length, where every element is
+    /// the given item.  A negative length gives an empty array.  This
+    /// may introduce further constraints into the circuit.
+    fn array_generator(&mut self, element: &Self::Type, item: &Self::Term, len: &Self::Int) -> Self::Array;
+
     /// Assert that a specific `condition` must be true for all
     /// possible interpretations of the circuit.  In effect, this
     /// places a constraint on the circuit that the given condition
-    /// holds.
-    fn assert(&mut self, condition: Self::Bool);
+    /// holds.  The descriptor identifies where the condition arose.
+    fn assert(&mut self, descriptor: VcDescriptor, condition: Self::Bool);
 
-    fn check(&self) -> Vec<Outcome>;
+    /// Check every asserted condition, returning the outcome for
+    /// each (in order of assertion).  An error indicates a failure
+    /// to communicate with the underlying solver.
+    fn check(&self) -> Result<Vec<(VcDescriptor,Outcome)>,Box<dyn Error>>;
 }
 
 pub trait Any : Clone {
@@ -101,11 +264,47 @@ pub trait Int : Clone {
     fn neg(&self) -> Self;
     fn add(&self, other: &Self) -> Self;
     fn sub(&self, other: &Self) -> Self;
+    /// Division truncates towards zero (as in Rust).
     fn div(&self, other: &Self) -> Self;
     fn mul(&self, other: &Self) -> Self;
+    /// Remainder takes the sign of the dividend (as in Rust).
     fn rem(&self, other: &Self) -> Self;
 }
 
+pub trait Array : Clone {
+    type Any;
+    type Int;
+
+    /// Construct an `Array` from arbitrary term.  Observe that this
+    /// will error if this doesn't make sense.
+    fn from_any(any: &Self::Any) -> Self;
+    /// Convert an array into an arbitrary term.
+    fn to_any(&self) -> Self::Any;
+
+    /// Get the number of elements in this array.
+    fn length(&self) -> Self::Int;
+    /// Get the element at a given index.  This is unspecified when
+    /// the index is out of bounds.
+    fn get(&self, index: &Self::Int) -> Self::Any;
+    /// Get the elements from `start` (inclusive) to `end`
+    /// (exclusive).
+    fn slice(&self, start: &Self::Int, end: &Self::Int) -> Self;
+}
+
+pub trait Tuple : Clone {
+    type Any;
+
+    /// Construct a `Tuple` from arbitrary term.  Observe that this
+    /// will error if this doesn't make sense.
+    fn from_any(any: &Self::Any) -> Self;
+    /// Convert a tuple into an arbitrary term.
+    fn to_any(&self) -> Self::Any;
+
+    /// Get the field at a given index of this tuple, which has `size`
+    /// fields in total.
+    fn get(&self, index: usize, size: usize) -> Self::Any;
+}
+
 pub trait Type : Clone {
     // What goes here?
 }
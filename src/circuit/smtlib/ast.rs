@@ -1,9 +1,12 @@
+use std::fmt;
+
 /// Set of built-in operators
-#[derive(Clone,Copy,Debug,PartialEq)]
+#[derive(Clone,Copy,Debug,Eq,Hash,PartialEq)]
 pub enum Op {
     Eq,
     Neq,
     // Arithmetical
+    Neg,
     Add,
     Sub,
     Mul,
@@ -19,6 +22,12 @@ pub enum Op {
     And,
     Implies,
     Not,
+    // Sequences
+    SeqLen,
+    SeqNth,
+    SeqExtract,
+    SeqUnit,
+    SeqConcat,
     // Other
     IfThenElse
 }
@@ -27,8 +36,9 @@ impl Op {
     /// Determine how many arguments are expected
     pub fn arity(&self) -> usize {
         match self {
-            Op::IfThenElse => 3,
-            Op::Not => 1,
+            Op::IfThenElse|Op::SeqExtract => 3,
+            Op::SeqNth => 2,
+            Op::Neg|Op::Not|Op::SeqLen|Op::SeqUnit => 1,
             _ => usize::MAX
         }
     }
@@ -36,7 +46,8 @@ impl Op {
     pub fn as_str(&self) -> &str {
         match self {
             Op::Eq => "=",
-            Op::Neq => "!=",
+            Op::Neq => "distinct",
+            Op::Neg => "-",
             Op::Add => "+",
             Op::Sub => "-",
             Op::Mul => "*",
@@ -50,12 +61,48 @@ impl Op {
             Op::And => "and",
             Op::Implies => "=>",
             Op::Not => "not",
+            Op::SeqLen => "seq.len",
+            Op::SeqNth => "seq.nth",
+            Op::SeqExtract => "seq.extract",
+            Op::SeqUnit => "seq.unit",
+            Op::SeqConcat => "seq.++",
             Op::IfThenElse => "ite"
         }
     }
+    /// Determine the operator corresponding to a given string and
+    /// number of arguments (if any).  The latter is necessary to
+    /// distinguish negation from subtraction.
+    pub fn from_symbol(s: &str, arity: usize) -> Option<Op> {
+        let op = match s {
+            "=" => Op::Eq,
+            "distinct" => Op::Neq,
+            "-" if arity == 1 => Op::Neg,
+            "+" => Op::Add,
+            "-" => Op::Sub,
+            "*" => Op::Mul,
+            "div" => Op::Div,
+            "mod" => Op::Mod,
+            ">" => Op::Gt,
+            ">=" => Op::GtEq,
+            "<" => Op::Lt,
+            "<=" => Op::LtEq,
+            "or" => Op::Or,
+            "and" => Op::And,
+            "=>" => Op::Implies,
+            "not" => Op::Not,
+            "seq.len" => Op::SeqLen,
+            "seq.nth" => Op::SeqNth,
+            "seq.extract" => Op::SeqExtract,
+            "seq.unit" => Op::SeqUnit,
+            "seq.++" => Op::SeqConcat,
+            "ite" => Op::IfThenElse,
+            _ => { return None; }
+        };
+        Some(op)
+    }
 }
 
-#[derive(Clone,Debug,PartialEq)]
+#[derive(Clone,Debug,Eq,Hash,PartialEq)]
 pub enum Expr {
     /// Int Literal
     Integer(usize),
@@ -64,20 +111,259 @@ pub enum Expr {
     /// Nary Expression
     Operator(Op,Vec<Expr>),
     /// Variable Access
-    VarAccess(String)
+    VarAccess(String),
+    /// Application of a declared function
+    Apply(String,Vec<Expr>),
+    /// Empty sequence of a given (sequence) sort
+    Empty(Sort),
+    /// Universal quantifier over one or more variables
+    Forall(Vec<(String,Sort)>,Box<Expr>)
 }
 
-#[derive(Clone,Debug,PartialEq)]
+impl Expr {
+    /// Determine the set of variables accessed within this
+    /// expression (in order of first access).
+    pub fn vars(&self) -> Vec<String> {
+        let mut vars = Vec::new();
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars(&self, vars: &mut Vec<String>) {
+        match self {
+            Expr::VarAccess(v) if !vars.contains(v) => vars.push(v.clone()),
+            Expr::Operator(_,args)|Expr::Apply(_,args) => {
+                for arg in args { arg.collect_vars(vars); }
+            }
+            Expr::Forall(params,body) => {
+                // Bound variables are not free
+                let mut inner = Vec::new();
+                body.collect_vars(&mut inner);
+                for v in inner {
+                    if !vars.contains(&v) && !params.iter().any(|(p,_)| *p == v) {
+                        vars.push(v);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Determine whether this expression is a (non-negative) integer
+    /// literal.
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Expr::Integer(_))
+    }
+
+    /// Determine whether this expression (or any subexpression)
+    /// requires non-linear arithmetic.  For example, `(* x y)` is
+    /// non-linear whilst `(* 2 y)` is not.
+    pub fn is_nonlinear(&self) -> bool {
+        match self {
+            Expr::Operator(Op::Mul,args) => {
+                args.iter().filter(|e| !e.is_integer()).count() > 1
+                    || args.iter().any(|e| e.is_nonlinear())
+            }
+            Expr::Operator(Op::Div|Op::Mod,args) => {
+                args.iter().skip(1).any(|e| !e.is_integer())
+                    || args.iter().any(|e| e.is_nonlinear())
+            }
+            Expr::Operator(_,args)|Expr::Apply(_,args) => {
+                args.iter().any(|e| e.is_nonlinear())
+            }
+            Expr::Forall(_,body) => body.is_nonlinear(),
+            _ => false
+        }
+    }
+
+    /// Determine whether this expression contains integer terms.
+    pub fn has_integers(&self) -> bool {
+        match self {
+            Expr::Integer(_) => true,
+            Expr::Operator(Op::Neg|Op::Add|Op::Sub|Op::Mul|Op::Div|Op::Mod,_) => true,
+            Expr::Operator(Op::Gt|Op::GtEq|Op::Lt|Op::LtEq,_) => true,
+            Expr::Operator(_,args)|Expr::Apply(_,args) => {
+                args.iter().any(|e| e.has_integers())
+            }
+            Expr::Forall(params,body) => {
+                params.iter().any(|(_,s)| *s == Sort::Int) || body.has_integers()
+            }
+            _ => false
+        }
+    }
+
+    /// Determine whether this expression uses theories beyond those
+    /// of the standard quantifier-free logics (i.e. sequences or
+    /// quantifiers).  Observe that tuples are accounted for by their
+    /// declarations.
+    pub fn has_extensions(&self) -> bool {
+        match self {
+            Expr::Empty(_)|Expr::Forall(..) => true,
+            Expr::Operator(Op::SeqLen|Op::SeqNth|Op::SeqExtract|Op::SeqUnit|Op::SeqConcat,_) => true,
+            Expr::Operator(_,args)|Expr::Apply(_,args) => {
+                args.iter().any(|e| e.has_extensions())
+            }
+            _ => false
+        }
+    }
+}
+
+#[derive(Clone,Debug,Eq,Hash,PartialEq)]
 pub enum Sort {
     Bool,
-    Int
+    Int,
+    /// Sequence of elements of a given sort
+    Seq(Box<Sort>),
+    /// Tuple with fields of the given sorts.  This is an instance of
+    /// the (parametric) datatype declared for tuples of that size.
+    Tuple(Vec<Sort>)
 }
 
+impl Sort {
+    /// Determine whether this sort uses theories beyond those of the
+    /// standard logics (i.e. sequences or datatypes).
+    pub fn has_extensions(&self) -> bool {
+        matches!(self,Sort::Seq(_)|Sort::Tuple(_))
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sort::Bool => write!(f,"Bool"),
+            Sort::Int => write!(f,"Int"),
+            Sort::Seq(e) => write!(f,"(Seq {e})"),
+            Sort::Tuple(fs) if fs.is_empty() => write!(f,"{}",tuple_name(0)),
+            Sort::Tuple(fs) => {
+                write!(f,"({}",tuple_name(fs.len()))?;
+                for s in fs { write!(f," {s}")?; }
+                write!(f,")")
+            }
+        }
+    }
+}
+
+#[derive(Clone,Debug,PartialEq)]
 pub enum Command {
+    SetOption(String,String),
+    SetLogic(String),
     DeclareFun(String,Vec<Sort>,Sort),
-    DeclareVar(String,Sort),
+    DeclareConst(String,Sort),
+    /// Declare the datatype for tuples of a given size.
+    DeclareTuple(usize),
+    /// Define one or more (mutually) recursive functions.
+    DefineFunsRec(Vec<FunDef>),
     Assert(Expr),
-    CheckSat
+    CheckSat,
+    GetModel,
+    GetInfo(String),
+    Push(usize),
+    Pop(usize),
+    Exit
+}
+
+/// Definition of a (possibly recursive) function, whose body refers
+/// to its parameters by name.
+#[derive(Clone,Debug,PartialEq)]
+pub struct FunDef {
+    pub name: String,
+    pub params: Vec<(String,Sort)>,
+    pub ret: Sort,
+    pub body: Expr
+}
+
+/// Words which are reserved in SMT-LIB 2.6 (i.e. keywords and
+/// command names), or which are defined in the theories used, and
+/// cannot therefore be declared.  This is in addition to the builtin
+/// operators.
+const RESERVED : &[&str] = &[
+    // Reserved words
+    "BINARY", "DECIMAL", "HEXADECIMAL", "NUMERAL", "STRING",
+    "_", "!", "as", "let", "exists", "forall", "match", "par",
+    // Command names
+    "assert", "check-sat", "check-sat-assuming", "declare-const",
+    "declare-datatype", "declare-datatypes", "declare-fun",
+    "declare-sort", "define-fun", "define-fun-rec", "define-funs-rec",
+    "define-sort", "echo", "exit", "get-assertions", "get-assignment",
+    "get-info", "get-model", "get-option", "get-proof",
+    "get-unsat-assumptions", "get-unsat-core", "get-value", "pop",
+    "push", "reset", "reset-assertions", "set-info", "set-logic",
+    "set-option",
+    // Theory symbols
+    "true", "false", "abs", "rem", "to_int", "to_real", "is_int",
+    "Bool", "Int", "Seq", "seq.empty"
+];
+
+// NOTE: `!` cannot appear in source-level names, hence the symbols
+// used for tuples cannot clash with them.
+
+/// Name of the datatype for tuples of a given size (e.g. `Tuple!2`).
+pub fn tuple_name(size: usize) -> String {
+    format!("Tuple!{size}")
+}
+
+/// Name of the constructor for tuples of a given size
+/// (e.g. `tuple!2`).
+pub fn tuple_constructor(size: usize) -> String {
+    format!("tuple!{size}")
+}
+
+/// Name of the selector for the `i`th field of tuples of a given size
+/// (e.g. `tuple!2.0`).
+pub fn tuple_selector(size: usize, i: usize) -> String {
+    format!("tuple!{size}.{i}")
+}
+
+/// Determine whether a given symbol is reserved (e.g. is a keyword or
+/// builtin operator), and hence cannot be declared.
+pub fn is_reserved(symbol: &str) -> bool {
+    RESERVED.contains(&symbol) || Op::from_symbol(symbol,2).is_some()
+}
+
+/// Determine the smallest standard logic which covers a given set of
+/// commands.  Commands using sequences, datatypes, quantifiers or
+/// recursive definitions fall outside the standard logics, and
+/// require `ALL`.  Otherwise, commands are
+/// quantifier free, and the logic is then refined depending on
+/// whether uninterpreted functions, integers or non-linear arithmetic
+/// are used.
+pub fn logic(commands: &[Command]) -> String {
+    let mut uf = false;
+    let mut ints = false;
+    let mut nonlinear = false;
+    let mut extensions = false;
+    //
+    for cmd in commands {
+        match cmd {
+            Command::DeclareFun(_,params,ret) => {
+                uf = true;
+                ints |= *ret == Sort::Int || params.contains(&Sort::Int);
+                extensions |= ret.has_extensions() || params.iter().any(|p| p.has_extensions());
+            }
+            Command::DeclareTuple(_)|Command::DefineFunsRec(_) => { extensions = true; }
+            Command::DeclareConst(_,sort) => {
+                ints |= *sort == Sort::Int;
+                extensions |= sort.has_extensions();
+            }
+            Command::Assert(e) => {
+                ints |= e.has_integers();
+                nonlinear |= e.is_nonlinear();
+                extensions |= e.has_extensions();
+            }
+            _ => {}
+        }
+    }
+    //
+    if extensions { return "ALL".to_string(); }
+    //
+    let mut logic = "QF_".to_string();
+    if uf || !ints { logic.push_str("UF"); }
+    if nonlinear {
+        logic.push_str("NIA");
+    } else if ints {
+        logic.push_str("LIA");
+    }
+    logic
 }
 
 pub struct Function {
@@ -1,6 +1,8 @@
+use std::collections::{HashMap,HashSet};
+use std::error::Error;
 use crate::circuit;
-use super::SmtLibWriter;
 use super::ast::*;
+use super::SExpr;
 use super::solver::{SmtOutcome,SmtSolver};
 
 use super::ast::Op::*;
@@ -12,18 +14,97 @@ use super::ast::Op::*;
 pub struct SmtLibCircuit<'a> {
     /// Set of asserted verification conditions.
     commands: Vec<Command>,
+    /// Descriptor for each `check-sat` command (in order).
+    descriptors: Vec<circuit::VcDescriptor>,
+    /// Constants declared so far, along with their sorts.
+    declared: HashMap<String,Sort>,
+    /// Maps declared constants back to their original names.
+    names: HashMap<String,String>,
+    /// Functions declared so far.
+    functions: HashSet<String>,
+    /// Sizes of the tuples whose datatypes have been declared so far.
+    tuples: HashSet<usize>,
+    /// Constants introduced for array generators, indexed by the
+    /// item and length they were generated from.
+    generators: HashMap<(Expr,Expr),Expr>,
     /// Smt Solver to use for discharging commands.
     solver: SmtSolver<'a>
 }
 
 impl<'a> SmtLibCircuit<'a> {
     pub fn new(solver: SmtSolver<'a>) -> Self {
-        Self{commands: Vec::new(),solver}
+        Self{commands: Vec::new(),descriptors: Vec::new(),declared: HashMap::new(),names: HashMap::new(),functions: HashSet::new(),tuples: HashSet::new(),generators: HashMap::new(),solver}
     }
 
-    pub fn discharge(&mut self, condition: Expr) {
+    /// Declare a constant of a given sort.  Since functions are
+    /// verified independently, their parameters may share names and
+    /// duplicate declarations are simply ignored.  However, a
+    /// redeclaration with a different sort is given a fresh name
+    /// (e.g. `x@1`).
+    fn declare_const(&mut self, name: &str, sort: Sort) -> Expr {
+        let mut i = 0;
+        let mut symbol = self.fresh(name,&mut i);
+        while let Some(s) = self.declared.get(&symbol) {
+            if *s == sort {
+                // Already declared
+                return Expr::VarAccess(symbol);
+            }
+            symbol = self.fresh(name,&mut i);
+        }
+        self.declared.insert(symbol.clone(),sort.clone());
+        self.names.insert(symbol.clone(),name.to_string());
+        self.commands.push(Command::DeclareConst(symbol.clone(),sort));
+        Expr::VarAccess(symbol)
+    }
+
+    /// Determine a symbol for a given name which is not reserved, and
+    /// does not clash with any declared function.  Symbols are tried
+    /// in order `x`, `x@1`, `x@2`, etc, starting from the `i`th.
+    fn fresh(&self, name: &str, i: &mut usize) -> String {
+        loop {
+            let symbol = if *i == 0 { name.to_string() } else { format!("{name}@{i}") };
+            *i += 1;
+            if !is_reserved(&symbol) && !self.functions.contains(&symbol) {
+                return symbol;
+            }
+        }
+    }
+
+    /// Get the commands constructed so far.
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Get the descriptors of all conditions asserted so far.
+    pub fn descriptors(&self) -> &[circuit::VcDescriptor] {
+        &self.descriptors
+    }
+
+    /// Check a given condition holds by asserting its negation in a
+    /// fresh scope.  Thus, each condition is checked independently of
+    /// the others, and produces exactly one `check-sat` response.
+    /// Should the condition not hold, a model is also requested.
+    pub fn discharge(&mut self, descriptor: circuit::VcDescriptor, condition: Expr) {
         let r = <Expr as circuit::Bool>::not(&condition);
+        self.descriptors.push(descriptor);
+        self.commands.push(Command::Push(1));
         self.commands.push(Command::Assert(r));
+        self.commands.push(Command::CheckSat);
+        self.commands.push(Command::GetModel);
+        self.commands.push(Command::Pop(1));
+    }
+
+    /// Construct a (source-level) model from that returned by the
+    /// solver, restricted to the given constants.
+    fn to_model(&self, assignments: &[(String,SExpr)], vars: &[String]) -> circuit::Model {
+        let mut model = circuit::Model::new();
+        for v in vars {
+            // NOTE: constants introduced internally have no name.
+            if let (Some((_,val)),Some(name)) = (assignments.iter().find(|(s,_)| s == v),self.names.get(v)) {
+                model.assign(name,&to_value(val));
+            }
+        }
+        model
     }
 }
 
@@ -33,6 +114,8 @@ impl<'a> circuit::Circuit for SmtLibCircuit<'a> {
     type Int = Expr;
     type Type = Sort;
     type Function = Function;
+    type Array = Expr;
+    type Tuple = Expr;
 
     fn from_bool(&self, val: bool) -> Self::Bool {
         Expr::Boolean(val)
@@ -43,20 +126,70 @@ impl<'a> circuit::Circuit for SmtLibCircuit<'a> {
     }
 
     fn declare_bool(&mut self, name: &str) -> Self::Bool {
-        self.commands.push(Command::DeclareVar(name.to_string(),Sort::Bool));
-	Expr::VarAccess(name.to_string())
+        self.declare_const(name,Sort::Bool)
     }
 
     fn declare_int(&mut self, name: &str) -> Self::Int {
-        self.commands.push(Command::DeclareVar(name.to_string(),Sort::Int));
-	Expr::VarAccess(name.to_string())
+        self.declare_const(name,Sort::Int)
     }
 
+    fn declare_array(&mut self, name: &str, element: &Self::Type) -> Self::Array {
+        self.declare_const(name,Sort::Seq(Box::new(element.clone())))
+    }
+
+    fn declare_tuple(&mut self, name: &str, fields: &[Self::Type]) -> Self::Tuple {
+        let sort = self.tuple_type(fields);
+        self.declare_const(name,sort)
+    }
+
+    /// A function with multiple returns is declared as returning a
+    /// tuple of them.
     fn declare_fn(&mut self, name: &str, params: &[Self::Type], rets: &[Self::Type]) -> Self::Function {
-        // For now
-        assert_eq!(rets.len(),1);
-        self.commands.push(Command::DeclareFun(name.to_string(),params.to_vec(),rets[0].clone()));
-        Function{name: name.to_string(),arity:params.len()}
+        assert!(!rets.is_empty());
+        let ret = match rets {
+            [r] => r.clone(),
+            _ => self.tuple_type(rets)
+        };
+        // Determine symbol which does not clash
+        let mut i = 0;
+        let mut symbol = self.fresh(name,&mut i);
+        while self.declared.contains_key(&symbol) {
+            symbol = self.fresh(name,&mut i);
+        }
+        self.functions.insert(symbol.clone());
+        self.commands.push(Command::DeclareFun(symbol.clone(),params.to_vec(),ret));
+        Function{name: symbol,arity:params.len()}
+    }
+
+    fn declare_param(&mut self, name: &str, _typ: &Self::Type) -> Self::Term {
+        // Parameters are bound by the definition, hence may shadow
+        // declared constants.
+        Expr::VarAccess(self.fresh(name,&mut 0))
+    }
+
+    /// The declaration of a defined function is replaced by its
+    /// definition.  Since definitions may be mutually recursive, they
+    /// are grouped into a single `define-funs-rec` command, which
+    /// follows every declaration they might use.
+    fn define_fn(&mut self, function: &Self::Function, params: &[(Self::Term,Self::Type)], body: &Self::Term) {
+        let i = self.commands.iter().position(|c| matches!(c,Command::DeclareFun(n,..) if *n == function.name)).unwrap();
+        let (name,ret) = match self.commands.remove(i) {
+            Command::DeclareFun(name,_,ret) => (name,ret),
+            _ => unreachable!()
+        };
+        let params = params.iter().map(|(p,s)| match p {
+            Expr::VarAccess(n) => (n.clone(),s.clone()),
+            _ => unreachable!()
+        }).collect();
+        let mut defs = match self.commands.iter().position(|c| matches!(c,Command::DefineFunsRec(_))) {
+            Some(j) => match self.commands.remove(j) {
+                Command::DefineFunsRec(defs) => defs,
+                _ => unreachable!()
+            }
+            None => Vec::new()
+        };
+        defs.push(FunDef{name,params,ret,body: body.clone()});
+        self.commands.push(Command::DefineFunsRec(defs));
     }
 
     fn bool_type(&self) -> Self::Type {
@@ -67,27 +200,133 @@ impl<'a> circuit::Circuit for SmtLibCircuit<'a> {
         Sort::Int
     }
 
-    fn assert(&mut self, condition: Self::Bool) {
-        SmtLibCircuit::discharge(self,condition);
+    fn array_type(&self, element: &Self::Type) -> Self::Type {
+        Sort::Seq(Box::new(element.clone()))
+    }
+
+    /// Tuples of each size are represented by a parametric datatype,
+    /// which is declared on first use.
+    fn tuple_type(&mut self, fields: &[Self::Type]) -> Self::Type {
+        if self.tuples.insert(fields.len()) {
+            self.commands.push(Command::DeclareTuple(fields.len()));
+        }
+        Sort::Tuple(fields.to_vec())
+    }
+
+    fn tuple_from(&mut self, fields: &[Self::Type], items: &[Self::Term]) -> Self::Tuple {
+        self.tuple_type(fields);
+        Expr::Apply(tuple_constructor(items.len()),items.to_vec())
+    }
+
+    fn array_from(&self, element: &Self::Type, items: &[Self::Term]) -> Self::Array {
+        let mut units : Vec<Expr> = items.iter().map(|i| Expr::Operator(SeqUnit,vec![i.clone()])).collect();
+        match units.len() {
+            0 => Expr::Empty(self.array_type(element)),
+            1 => units.pop().unwrap(),
+            _ => Expr::Operator(SeqConcat,units)
+        }
+    }
+
+    /// An array generator `[e;n]` has no direct representation using
+    /// sequences.  Instead, a fresh constant `g` is introduced along
+    /// with an axiom that `|g| == n` and every element of `g` is `e`.
+    /// This requires a quantifier.
+    fn array_generator(&mut self, element: &Self::Type, item: &Self::Term, len: &Self::Int) -> Self::Array {
+        let key = (item.clone(),len.clone());
+        if let Some(g) = self.generators.get(&key) {
+            return g.clone();
+        }
+        // NOTE: `!` cannot appear in source-level names
+        let symbol = format!("array!{}",self.generators.len());
+        let sort = self.array_type(element);
+        self.declared.insert(symbol.clone(),sort.clone());
+        self.commands.push(Command::DeclareConst(symbol.clone(),sort));
+        let g = Expr::VarAccess(symbol);
+        // Construct axiom
+        let i = Expr::VarAccess("i!".to_string());
+        let g_len = Expr::Operator(SeqLen,vec![g.clone()]);
+        let n = Expr::Operator(IfThenElse,vec![
+            Expr::Operator(GtEq,vec![len.clone(),Expr::Integer(0)]),
+            len.clone(),
+            Expr::Integer(0)]);
+        let in_bounds = Expr::Operator(And,vec![
+            Expr::Operator(LtEq,vec![Expr::Integer(0),i.clone()]),
+            Expr::Operator(Lt,vec![i.clone(),g_len.clone()])]);
+        let ith = Expr::Operator(Eq,vec![Expr::Operator(SeqNth,vec![g.clone(),i]),item.clone()]);
+        let elements = Expr::Forall(vec![("i!".to_string(),Sort::Int)],Box::new(
+            Expr::Operator(Implies,vec![in_bounds,ith])));
+        let axiom = Expr::Operator(And,vec![Expr::Operator(Eq,vec![g_len,n]),elements]);
+        self.commands.push(Command::Assert(axiom));
+        self.generators.insert(key,g.clone());
+        g
+    }
+
+    fn assert(&mut self, descriptor: circuit::VcDescriptor, condition: Self::Bool) {
+        SmtLibCircuit::discharge(self,descriptor,condition);
     }
 
-    fn check(&self) -> Vec<circuit::Outcome> {
-        let results = self.solver.check(&self.commands);
+    fn check(&self) -> Result<Vec<(circuit::VcDescriptor,circuit::Outcome)>,Box<dyn Error>> {
+        let results = self.solver.check(&self.commands)?;
+        // Determine constants involved in each check
+        let mut vars = Vec::new();
+        for (i,cmd) in self.commands.iter().enumerate() {
+            if let (Command::CheckSat,Some(Command::Assert(e))) = (cmd,self.commands.get(i.wrapping_sub(1))) {
+                vars.push(e.vars());
+            }
+        }
         //
-        results.iter().map(|o| {
-            match o {
-                SmtOutcome::Sat => circuit::Outcome::Invalid,
+        Ok(results.iter().zip(vars).zip(&self.descriptors).map(|((o,vs),d)| {
+            let outcome = match o {
+                SmtOutcome::Sat(m) => circuit::Outcome::Invalid(self.to_model(m,&vs)),
                 SmtOutcome::Unsat => circuit::Outcome::Valid,
-                _ => circuit::Outcome::Unknown
+                SmtOutcome::Timeout => circuit::Outcome::Timeout,
+                SmtOutcome::Maybe => circuit::Outcome::Unknown
+            };
+            (*d,outcome)
+        }).collect())
+    }
+}
+
+/// Convert a value from a model into a human-readable form.  For
+/// example, `(- 1)` becomes `-1`, `(tuple!2 1 2)` becomes `(1, 2)`
+/// and `(seq.++ (seq.unit 1) (seq.unit 2))` becomes `[1, 2]`.  Any
+/// constructor starting `tuple!` is a tuple, since the Z3 backend
+/// numbers its tuple datatypes differently.
+pub fn to_value(value: &SExpr) -> String {
+    match value.as_list() {
+        Some([SExpr::Atom(m),SExpr::Atom(n)]) if m == "-" => format!("-{n}"),
+        Some([SExpr::Atom(c),fs@..]) if c.starts_with("tuple!") => {
+            let fields : Vec<String> = fs.iter().map(to_value).collect();
+            format!("({})",fields.join(", "))
+        }
+        _ => match to_items(value) {
+            Some(items) => format!("[{}]",items.join(", ")),
+            None => value.to_string()
+        }
+    }
+}
+
+/// Convert a sequence value from a model into its items (if it is
+/// one).
+fn to_items(value: &SExpr) -> Option<Vec<String>> {
+    match value.as_list()? {
+        [SExpr::Atom(a),SExpr::Atom(e),_] if a == "as" && e == "seq.empty" => Some(Vec::new()),
+        [SExpr::Atom(u),item] if u == "seq.unit" => Some(vec![to_value(item)]),
+        [SExpr::Atom(c),seqs@..] if c == "seq.++" => {
+            let mut items = Vec::new();
+            for s in seqs {
+                items.extend(to_items(s)?);
             }
-        }).collect()
+            Some(items)
+        }
+        _ => None
     }
 }
 
 // =============================================================================
 // Any
 // =============================================================================
-impl<'a> circuit::Any for Expr {
+impl circuit::Any for Expr {
     type Bool = Expr;
 
     fn eq(&self, other: &Self) -> Self::Bool {
@@ -102,7 +341,7 @@ impl<'a> circuit::Any for Expr {
 // =============================================================================
 // Boolean
 // =============================================================================
-impl<'a> circuit::Bool for Expr {
+impl circuit::Bool for Expr {
     type Any = Expr;
 
     fn from_any(any: &Self::Any) -> Self {
@@ -144,7 +383,10 @@ impl circuit::Int for Expr {
         self.clone()
     }
     // Comparators
-    fn non_zero(&self) -> Self::Bool { todo!() }
+    fn non_zero(&self) -> Self::Bool {
+        let eq = Expr::Operator(Eq,vec![self.clone(),Expr::Integer(0)]);
+        Expr::Operator(Not,vec![eq])
+    }
     fn lt(&self, other: &Self) -> Self::Bool {
         Expr::Operator(Lt,vec![self.clone(),other.clone()])
     }
@@ -158,7 +400,7 @@ impl circuit::Int for Expr {
         Expr::Operator(GtEq,vec![self.clone(),other.clone()])
     }
     // Arithmetic OperatorOperators
-    fn neg(&self) -> Self { Expr::Operator(Sub,vec![self.clone()]) }
+    fn neg(&self) -> Self { Expr::Operator(Neg,vec![self.clone()]) }
     fn add(&self, other: &Self) -> Self {
         Expr::Operator(Add,vec![self.clone(),other.clone()])
     }
@@ -166,13 +408,66 @@ impl circuit::Int for Expr {
         Expr::Operator(Sub,vec![self.clone(),other.clone()])
     }
     fn div(&self, other: &Self) -> Self {
-        Expr::Operator(Div,vec![self.clone(),other.clone()])
+        truncate(Div,self,other)
     }
     fn mul(&self, other: &Self) -> Self {
         Expr::Operator(Mul,vec![self.clone(),other.clone()])
     }
     fn rem(&self, other: &Self) -> Self {
-        Expr::Operator(Mod,vec![self.clone(),other.clone()])
+        truncate(Mod,self,other)
+    }
+}
+
+/// Apply a Euclidean operator (i.e. `div` or `mod`) such that it
+/// truncates towards zero instead.  These agree for a non-negative
+/// dividend (e.g. a literal), and otherwise `x op y == -((-x) op y)`.
+fn truncate(op: Op, x: &Expr, y: &Expr) -> Expr {
+    let pos = Expr::Operator(op,vec![x.clone(),y.clone()]);
+    if x.is_integer() { return pos; }
+    let neg = Expr::Operator(Neg,vec![Expr::Operator(op,vec![Expr::Operator(Neg,vec![x.clone()]),y.clone()])]);
+    let cond = Expr::Operator(GtEq,vec![x.clone(),Expr::Integer(0)]);
+    Expr::Operator(IfThenElse,vec![cond,pos,neg])
+}
+
+// =============================================================================
+// Array
+// =============================================================================
+impl circuit::Array for Expr {
+    type Any = Expr;
+    type Int = Expr;
+
+    fn from_any(any: &Self::Any) -> Self {
+        any.clone()
+    }
+    fn to_any(&self) -> Self::Any {
+        self.clone()
+    }
+    fn length(&self) -> Self::Int {
+        Expr::Operator(SeqLen,vec![self.clone()])
+    }
+    fn get(&self, index: &Self::Int) -> Self::Any {
+        Expr::Operator(SeqNth,vec![self.clone(),index.clone()])
+    }
+    fn slice(&self, start: &Self::Int, end: &Self::Int) -> Self {
+        let len = Expr::Operator(Sub,vec![end.clone(),start.clone()]);
+        Expr::Operator(SeqExtract,vec![self.clone(),start.clone(),len])
+    }
+}
+
+// =============================================================================
+// Tuple
+// =============================================================================
+impl circuit::Tuple for Expr {
+    type Any = Expr;
+
+    fn from_any(any: &Self::Any) -> Self {
+        any.clone()
+    }
+    fn to_any(&self) -> Self::Any {
+        self.clone()
+    }
+    fn get(&self, index: usize, size: usize) -> Self::Any {
+        Expr::Apply(tuple_selector(size,index),vec![self.clone()])
     }
 }
 
@@ -194,6 +489,7 @@ impl circuit::Function for Function {
     }
 
     fn invoke(&self, args: &[Self::Any]) -> Self::Any {
-        todo!()
+        assert_eq!(args.len(),self.arity);
+        Expr::Apply(self.name.clone(),args.to_vec())
     }
 }
@@ -1,9 +1,15 @@
 mod ast;
 mod circuit;
 mod printer;
+mod profile;
+mod reader;
+mod session;
 mod solver;
 
 pub use ast::*;
 pub use circuit::*;
 pub use printer::*;
+pub use profile::*;
+pub use reader::*;
+pub use session::*;
 pub use solver::*;
@@ -21,24 +21,94 @@ impl<T:Write> SmtLibWriter<T> {
 
     fn write_command(&mut self, cmd: &Command) -> Result<()> {
         match cmd {
+            Command::SetOption(opt,val) => writeln!(self.out,"(set-option {opt} {val})"),
+            Command::SetLogic(logic) => self.write_setlogic(logic),
             Command::Assert(expr) => self.write_assert(expr),
-            Command::DeclareVar(name,typ) => self.write_declarevar(name,typ),
+            Command::DeclareConst(name,typ) => self.write_declareconst(name,typ),
             Command::DeclareFun(name,params,ret) => self.write_declarefun(name,params,ret),
-            Command::CheckSat => self.write_checksat()
+            Command::DeclareTuple(n) => self.write_declaretuple(*n),
+            Command::DefineFunsRec(defs) => self.write_definefunsrec(defs),
+            Command::CheckSat => self.write_checksat(),
+            Command::GetModel => writeln!(self.out,"(get-model)"),
+            Command::GetInfo(key) => writeln!(self.out,"(get-info {key})"),
+            Command::Push(n) => writeln!(self.out,"(push {n})"),
+            Command::Pop(n) => writeln!(self.out,"(pop {n})"),
+            Command::Exit => writeln!(self.out,"(exit)")
         }
     }
 
-    fn write_declarevar(&mut self, name: &str, typ: &Sort) -> Result<()> {
-        writeln!(self.out,"(declare-var {name} {typ:?})")
+    fn write_setlogic(&mut self, logic: &str) -> Result<()> {
+        writeln!(self.out,"(set-logic {logic})")
+    }
+
+    fn write_declareconst(&mut self, name: &str, typ: &Sort) -> Result<()> {
+        writeln!(self.out,"(declare-const {name} {typ})")
     }
 
     fn write_declarefun(&mut self, name: &str, params: &[Sort], ret: &Sort) -> Result<()> {
         write!(self.out,"(declare-fun {name} (")?;
-        for i in 0..params.len() {
-            if i != 0 { write!(self.out,",")?; }
-            write!(self.out,"{:?}",params[i])?;
+        for (i,p) in params.iter().enumerate() {
+            if i != 0 { write!(self.out," ")?; }
+            write!(self.out,"{p}")?;
+        }
+        writeln!(self.out,") {ret})")
+    }
+
+    /// Write the declaration of a (parametric) datatype for tuples of
+    /// a given size.  For example, for pairs this gives:
+    ///
+    /// ```text
+    /// (declare-datatypes ((Tuple!2 2)) ((par (T0 T1) ((tuple!2 (tuple!2.0 T0) (tuple!2.1 T1))))))
+    /// ```
+    fn write_declaretuple(&mut self, n: usize) -> Result<()> {
+        write!(self.out,"(declare-datatypes (({} {n})) ((par (",tuple_name(n))?;
+        for i in 0..n {
+            if i != 0 { write!(self.out," ")?; }
+            write!(self.out,"T{i}")?;
+        }
+        write!(self.out,") (({}",tuple_constructor(n))?;
+        for i in 0..n {
+            write!(self.out," ({} T{i})",tuple_selector(n,i))?;
+        }
+        writeln!(self.out,")))))")
+    }
+
+    /// Write the definition of one or more (mutually) recursive
+    /// functions.  A single function is written using
+    /// `define-fun-rec`, whilst several are written using
+    /// `define-funs-rec`.
+    fn write_definefunsrec(&mut self, defs: &[FunDef]) -> Result<()> {
+        if let [def] = defs {
+            write!(self.out,"(define-fun-rec {} ",def.name)?;
+            self.write_signature(def)?;
+            write!(self.out," ")?;
+            self.write_expr(&def.body)?;
+            return writeln!(self.out,")");
+        }
+        write!(self.out,"(define-funs-rec (")?;
+        for (i,def) in defs.iter().enumerate() {
+            if i != 0 { write!(self.out," ")?; }
+            write!(self.out,"({} ",def.name)?;
+            self.write_signature(def)?;
+            write!(self.out,")")?;
         }
-        writeln!(self.out,") {ret:?})")
+        write!(self.out,") (")?;
+        for (i,def) in defs.iter().enumerate() {
+            if i != 0 { write!(self.out," ")?; }
+            self.write_expr(&def.body)?;
+        }
+        writeln!(self.out,"))")
+    }
+
+    /// Write the parameters and return sort of a function definition
+    /// (e.g. `((x Int) (y Int)) Int`).
+    fn write_signature(&mut self, def: &FunDef) -> Result<()> {
+        write!(self.out,"(")?;
+        for (i,(n,s)) in def.params.iter().enumerate() {
+            if i != 0 { write!(self.out," ")?; }
+            write!(self.out,"({n} {s})")?;
+        }
+        write!(self.out,") {}",def.ret)
     }
 
     fn write_checksat(&mut self) -> Result<()> {
@@ -56,8 +126,35 @@ impl<T:Write> SmtLibWriter<T> {
             Expr::Integer(i) => { write!(self.out,"{i}") }
             Expr::Boolean(b) => { write!(self.out,"{b}") }
             Expr::VarAccess(n) => { write!(self.out,"{n}") }
-            Expr::Operator(op,args) => self.write_nary(op,args)
+            Expr::Operator(op,args) => self.write_nary(op,args),
+            Expr::Apply(name,args) => self.write_apply(name,args),
+            Expr::Empty(sort) => { write!(self.out,"(as seq.empty {sort})") }
+            Expr::Forall(params,body) => self.write_forall(params,body)
+        }
+    }
+
+    fn write_forall(&mut self, params: &[(String,Sort)], body: &Expr) -> Result<()> {
+        write!(self.out,"(forall (")?;
+        for (i,(n,s)) in params.iter().enumerate() {
+            if i != 0 { write!(self.out," ")?; }
+            write!(self.out,"({n} {s})")?;
         }
+        write!(self.out,") ")?;
+        self.write_expr(body)?;
+        write!(self.out,")")
+    }
+
+    fn write_apply(&mut self, name: &str, args: &[Expr]) -> Result<()> {
+        // Nullary functions are applied without brackets
+        if args.is_empty() {
+            return write!(self.out,"{name}");
+        }
+        write!(self.out,"({name}")?;
+        for arg in args {
+            write!(self.out," ")?;
+            self.write_expr(arg)?;
+        }
+        write!(self.out,")")
     }
 
     fn write_nary(&mut self, op: &Op, args: &[Expr]) -> Result<()> {
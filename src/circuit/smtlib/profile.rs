@@ -0,0 +1,115 @@
+use std::fmt;
+use crate::circuit::Limits;
+
+/// Identifies a particular SMT solver, and captures what is needed to
+/// run it interactively: its default executable, the command-line
+/// flags which put it into SMT-LIB mode, the options it should be
+/// given, and the logics it supports.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum SolverProfile {
+    Z3,
+    Cvc5,
+    Yices,
+    Bitwuzla,
+    /// Some other solver which accepts SMT-LIB on stdin without any
+    /// additional flags.
+    Custom
+}
+
+/// Logics supported by Yices (which has no support for datatypes).
+const YICES_LOGICS : &[&str] = &["QF_UF","QF_LIA","QF_UFLIA","QF_NIA","QF_UFNIA"];
+
+/// Logics supported by Bitwuzla (which has no support for integers).
+const BITWUZLA_LOGICS : &[&str] = &["QF_UF"];
+
+impl SolverProfile {
+    /// Names of all profiles, as accepted by `from_name()`.
+    pub const NAMES : &'static [&'static str] = &["z3","cvc5","yices","bitwuzla","custom"];
+
+    /// Determine the profile with a given name (if any).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "z3" => Some(SolverProfile::Z3),
+            "cvc5" => Some(SolverProfile::Cvc5),
+            "yices" => Some(SolverProfile::Yices),
+            "bitwuzla" => Some(SolverProfile::Bitwuzla),
+            "custom" => Some(SolverProfile::Custom),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolverProfile::Z3 => "z3",
+            SolverProfile::Cvc5 => "cvc5",
+            SolverProfile::Yices => "yices",
+            SolverProfile::Bitwuzla => "bitwuzla",
+            SolverProfile::Custom => "custom"
+        }
+    }
+
+    /// Name of the executable to run by default (if known).
+    pub fn executable(&self) -> Option<&'static str> {
+        match self {
+            SolverProfile::Z3 => Some("z3"),
+            SolverProfile::Cvc5 => Some("cvc5"),
+            SolverProfile::Yices => Some("yices-smt2"),
+            SolverProfile::Bitwuzla => Some("bitwuzla"),
+            SolverProfile::Custom => None
+        }
+    }
+
+    /// Command-line arguments needed to run the solver interactively
+    /// (i.e. reading SMT-LIB commands from stdin).
+    pub fn args(&self) -> &'static [&'static str] {
+        match self {
+            SolverProfile::Z3 => &["--smt2","--in"],
+            SolverProfile::Cvc5 => &["--lang=smt2","--incremental"],
+            SolverProfile::Yices => &["--incremental"],
+            SolverProfile::Bitwuzla => &["--lang","smt2"],
+            SolverProfile::Custom => &[]
+        }
+    }
+
+    /// Options to set at the start of every session, including those
+    /// which enforce the given limits on each query.  Limits which a
+    /// solver cannot enforce itself are omitted.
+    pub fn options(&self, limits: &Limits) -> Vec<(String,String)> {
+        let mut options = vec![(":produce-models".to_string(),"true".to_string())];
+        let (timeout,rlimit) = match self {
+            SolverProfile::Z3 => (Some(":timeout"),Some(":rlimit")),
+            SolverProfile::Cvc5 => (Some(":tlimit-per"),Some(":rlimit-per")),
+            SolverProfile::Bitwuzla => (Some(":time-limit-per"),None),
+            _ => (None,None)
+        };
+        if let (Some(opt),Some(ms)) = (timeout,limits.timeout) {
+            options.push((opt.to_string(),ms.to_string()));
+        }
+        if let (Some(opt),Some(n)) = (rlimit,limits.rlimit) {
+            options.push((opt.to_string(),n.to_string()));
+        }
+        options
+    }
+
+    /// Determine whether this solver supports a given logic.  Unless
+    /// known otherwise, a solver is assumed to support all logics.
+    pub fn supports(&self, logic: &str) -> bool {
+        match self {
+            SolverProfile::Yices => YICES_LOGICS.contains(&logic),
+            SolverProfile::Bitwuzla => BITWUZLA_LOGICS.contains(&logic),
+            _ => true
+        }
+    }
+
+    /// Determine whether this solver is known to support recursive
+    /// function definitions (i.e. `define-fun-rec`).
+    pub fn supports_recursion(&self) -> bool {
+        matches!(self,SolverProfile::Z3|SolverProfile::Cvc5)
+    }
+}
+
+impl fmt::Display for SolverProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,"{}",self.name())
+    }
+}
@@ -0,0 +1,418 @@
+use std::fmt;
+use std::error::Error;
+use super::ast::*;
+
+// =============================================================================
+// S-Expressions
+// =============================================================================
+
+/// Represents an (untyped) S-Expression, which is the basic building
+/// block of all SMT-LIB scripts and solver responses.
+#[derive(Clone,Debug,PartialEq)]
+pub enum SExpr {
+    /// A symbol, keyword or numeral (e.g. `x`, `:status` or `123`).
+    Atom(String),
+    /// A string literal (excluding its enclosing quotes).
+    String(String),
+    /// A list of zero or more s-expressions.
+    List(Vec<SExpr>)
+}
+
+impl SExpr {
+    /// Get this s-expression as an atom (if it is one).
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            SExpr::Atom(s) => Some(s),
+            _ => None
+        }
+    }
+
+    /// Get this s-expression as a list (if it is one).
+    pub fn as_list(&self) -> Option<&[SExpr]> {
+        match self {
+            SExpr::List(l) => Some(l),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for SExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SExpr::Atom(s) => write!(f,"{s}"),
+            SExpr::String(s) => write!(f,"\"{}\"",s.replace('"',"\"\"")),
+            SExpr::List(l) => {
+                write!(f,"(")?;
+                for (i,e) in l.iter().enumerate() {
+                    if i != 0 { write!(f," ")?; }
+                    write!(f,"{e}")?;
+                }
+                write!(f,")")
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Read Error
+// =============================================================================
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct ReadError {
+    /// Character offset at which the error arose.
+    pub offset: usize,
+    pub message: String,
+    /// Indicates the error arose because the input ended early, and
+    /// hence may be resolved by reading further input.
+    incomplete: bool
+}
+
+impl ReadError {
+    pub fn new(offset: usize, message: &str) -> Self {
+        Self{offset,message: message.to_string(),incomplete: false}
+    }
+
+    /// Construct an error arising from the input ending early.
+    pub fn incomplete(offset: usize, message: &str) -> Self {
+        Self{offset,message: message.to_string(),incomplete: true}
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,"{} (at offset {})",self.message,self.offset)
+    }
+}
+
+impl Error for ReadError {
+
+}
+
+// =============================================================================
+// Reader
+// =============================================================================
+
+/// Responsible for reading SMT-LIB scripts (or solver responses).
+/// This is the inverse of `SmtLibWriter`, though it is not a complete
+/// implementation of the SMT-LIB standard.  Rather, it covers those
+/// commands and expressions produced by the writer.
+pub struct SmtLibReader {
+    chars: Vec<char>,
+    index: usize
+}
+
+impl SmtLibReader {
+    pub fn new(input: &str) -> Self {
+        Self{chars: input.chars().collect(), index: 0}
+    }
+
+    /// Read all remaining commands from the input.
+    pub fn read(self) -> Result<Vec<Command>,ReadError> {
+        let mut commands = Vec::new();
+        for sexpr in self.read_all()? {
+            commands.push(to_command(&sexpr)?);
+        }
+        Ok(commands)
+    }
+
+    /// Read all remaining s-expressions from the input.
+    pub fn read_all(mut self) -> Result<Vec<SExpr>,ReadError> {
+        let mut sexprs = Vec::new();
+        while let Some(sexpr) = self.read_next()? {
+            sexprs.push(sexpr);
+        }
+        Ok(sexprs)
+    }
+
+    /// Get the (character) position reached in the input.
+    pub fn position(&self) -> usize {
+        self.index
+    }
+
+    /// Read the next s-expression from the input, or `None` if the
+    /// end has been reached.
+    pub fn read_next(&mut self) -> Result<Option<SExpr>,ReadError> {
+        self.skip_whitespace();
+        if self.index >= self.chars.len() {
+            Ok(None)
+        } else {
+            Ok(Some(self.read_sexpr()?))
+        }
+    }
+
+    // =========================================================================
+    // Internal
+    // =========================================================================
+
+    fn read_sexpr(&mut self) -> Result<SExpr,ReadError> {
+        self.skip_whitespace();
+        //
+        match self.chars.get(self.index) {
+            None => Err(ReadError::incomplete(self.index,"unexpected end of input")),
+            Some('(') => self.read_list(),
+            Some(')') => Err(ReadError::new(self.index,"unexpected `)`")),
+            Some('"') => self.read_string(),
+            Some('|') => self.read_quoted_symbol(),
+            Some(_) => Ok(self.read_atom())
+        }
+    }
+
+    fn read_list(&mut self) -> Result<SExpr,ReadError> {
+        // Skip opening bracket
+        self.index += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.chars.get(self.index) {
+                None => {
+                    return Err(ReadError::incomplete(self.index,"unterminated list"));
+                }
+                Some(')') => {
+                    self.index += 1;
+                    return Ok(SExpr::List(items));
+                }
+                Some(_) => items.push(self.read_sexpr()?)
+            }
+        }
+    }
+
+    /// Read a string literal, where an embedded quote is written as
+    /// two consecutive quotes.
+    fn read_string(&mut self) -> Result<SExpr,ReadError> {
+        let start = self.index;
+        let mut s = String::new();
+        self.index += 1;
+        loop {
+            match self.chars.get(self.index) {
+                None => {
+                    return Err(ReadError::incomplete(start,"unterminated string"));
+                }
+                Some('"') if self.chars.get(self.index+1) == Some(&'"') => {
+                    s.push('"');
+                    self.index += 2;
+                }
+                Some('"') => {
+                    self.index += 1;
+                    return Ok(SExpr::String(s));
+                }
+                Some(c) => {
+                    s.push(*c);
+                    self.index += 1;
+                }
+            }
+        }
+    }
+
+    /// Read a quoted symbol (e.g. `|hello world|`), which is
+    /// returned with its enclosing bars.
+    fn read_quoted_symbol(&mut self) -> Result<SExpr,ReadError> {
+        let start = self.index;
+        self.index += 1;
+        while self.index < self.chars.len() && self.chars[self.index] != '|' {
+            self.index += 1;
+        }
+        if self.index >= self.chars.len() {
+            return Err(ReadError::incomplete(start,"unterminated symbol"));
+        }
+        self.index += 1;
+        Ok(SExpr::Atom(self.chars[start..self.index].iter().collect()))
+    }
+
+    fn read_atom(&mut self) -> SExpr {
+        let start = self.index;
+        while self.index < self.chars.len() {
+            let c = self.chars[self.index];
+            if c.is_whitespace() || c == '(' || c == ')' || c == ';' || c == '"' {
+                break;
+            }
+            self.index += 1;
+        }
+        SExpr::Atom(self.chars[start..self.index].iter().collect())
+    }
+
+    /// Skip whitespace and comments (which run from `;` to the end of
+    /// the line).
+    fn skip_whitespace(&mut self) {
+        while self.index < self.chars.len() {
+            let c = self.chars[self.index];
+            if c == ';' {
+                while self.index < self.chars.len() && self.chars[self.index] != '\n' {
+                    self.index += 1;
+                }
+            } else if c.is_whitespace() {
+                self.index += 1;
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+// =============================================================================
+// Conversion
+// =============================================================================
+
+/// Convert an s-expression into a command.
+pub fn to_command(sexpr: &SExpr) -> Result<Command,ReadError> {
+    let items = match sexpr.as_list() {
+        Some(items) if !items.is_empty() => items,
+        _ => { return Err(error(&format!("invalid command {sexpr}"))); }
+    };
+    //
+    match (items[0].as_atom(),&items[1..]) {
+        (Some("set-option"),[SExpr::Atom(o),SExpr::Atom(v)]) => {
+            Ok(Command::SetOption(o.clone(),v.clone()))
+        }
+        (Some("set-logic"),[SExpr::Atom(l)]) => Ok(Command::SetLogic(l.clone())),
+        (Some("declare-const"),[SExpr::Atom(n),s]) => {
+            Ok(Command::DeclareConst(n.clone(),to_sort(s)?))
+        }
+        (Some("declare-fun"),[SExpr::Atom(n),SExpr::List(ps),r]) => {
+            let params = ps.iter().map(to_sort).collect::<Result<Vec<_>,_>>()?;
+            Ok(Command::DeclareFun(n.clone(),params,to_sort(r)?))
+        }
+        (Some("declare-datatypes"),[SExpr::List(ds),_]) => {
+            // NOTE: only the datatypes declared for tuples are supported
+            match ds.as_slice() {
+                [SExpr::List(d)] => match d.as_slice() {
+                    [SExpr::Atom(n),SExpr::Atom(k)] if *n == tuple_name(to_numeral(k)?) => {
+                        Ok(Command::DeclareTuple(to_numeral(k)?))
+                    }
+                    _ => Err(error(&format!("unsupported datatype {sexpr}")))
+                }
+                _ => Err(error(&format!("unsupported datatype {sexpr}")))
+            }
+        }
+        (Some("define-fun-rec"),[SExpr::Atom(n),ps,r,b]) => {
+            Ok(Command::DefineFunsRec(vec![to_fundef(n,ps,r,b)?]))
+        }
+        (Some("define-funs-rec"),[SExpr::List(sigs),SExpr::List(bodies)]) if sigs.len() == bodies.len() => {
+            let mut defs = Vec::new();
+            for (sig,b) in sigs.iter().zip(bodies) {
+                match sig.as_list() {
+                    Some([SExpr::Atom(n),ps,r]) => defs.push(to_fundef(n,ps,r,b)?),
+                    _ => { return Err(error(&format!("invalid command {sexpr}"))); }
+                }
+            }
+            Ok(Command::DefineFunsRec(defs))
+        }
+        (Some("assert"),[e]) => Ok(Command::Assert(to_expr(e)?)),
+        (Some("check-sat"),[]) => Ok(Command::CheckSat),
+        (Some("push"),[SExpr::Atom(n)]) => Ok(Command::Push(to_numeral(n)?)),
+        (Some("pop"),[SExpr::Atom(n)]) => Ok(Command::Pop(to_numeral(n)?)),
+        (Some("exit"),[]) => Ok(Command::Exit),
+        (Some("get-model"),[]) => Ok(Command::GetModel),
+        (Some("get-info"),[SExpr::Atom(k)]) => Ok(Command::GetInfo(k.clone())),
+        _ => Err(error(&format!("invalid command {sexpr}")))
+    }
+}
+
+/// Convert the components of a (recursive) function definition into
+/// a function definition.
+fn to_fundef(name: &str, params: &SExpr, ret: &SExpr, body: &SExpr) -> Result<FunDef,ReadError> {
+    let mut ps = Vec::new();
+    for p in params.as_list().unwrap_or(&[]) {
+        match p.as_list() {
+            Some([SExpr::Atom(n),s]) => ps.push((n.clone(),to_sort(s)?)),
+            _ => { return Err(error(&format!("invalid parameter {p}"))); }
+        }
+    }
+    Ok(FunDef{name: name.to_string(),params: ps,ret: to_sort(ret)?,body: to_expr(body)?})
+}
+
+/// Convert a response to `(get-model)` into a list of assignments
+/// from constants to values.  For example, `((define-fun x () Int 1))`
+/// assigns `1` to `x`.  Older versions of Z3 prefix the response with
+/// `model`, and function definitions are ignored.  This returns `None`
+/// if the response is not a model (e.g. is an error).
+pub fn to_model(sexpr: &SExpr) -> Option<Vec<(String,SExpr)>> {
+    let mut items = sexpr.as_list()?;
+    if items.first().and_then(|i| i.as_atom()) == Some("model") {
+        items = &items[1..];
+    }
+    let mut model = Vec::new();
+    for item in items {
+        match item.as_list()? {
+            [SExpr::Atom(d),SExpr::Atom(n),SExpr::List(ps),_,v] if d == "define-fun" => {
+                if ps.is_empty() { model.push((n.clone(),v.clone())); }
+            }
+            _ => { return None; }
+        }
+    }
+    Some(model)
+}
+
+/// Convert an s-expression into a sort.
+pub fn to_sort(sexpr: &SExpr) -> Result<Sort,ReadError> {
+    match sexpr {
+        SExpr::Atom(s) if s == "Bool" => Ok(Sort::Bool),
+        SExpr::Atom(s) if s == "Int" => Ok(Sort::Int),
+        SExpr::Atom(s) if *s == tuple_name(0) => Ok(Sort::Tuple(Vec::new())),
+        SExpr::List(items) => match items.as_slice() {
+            [SExpr::Atom(s),e] if s == "Seq" => Ok(Sort::Seq(Box::new(to_sort(e)?))),
+            [SExpr::Atom(s),fs@..] if *s == tuple_name(fs.len()) => {
+                Ok(Sort::Tuple(fs.iter().map(to_sort).collect::<Result<Vec<_>,_>>()?))
+            }
+            _ => Err(error(&format!("invalid sort {sexpr}")))
+        }
+        _ => Err(error(&format!("invalid sort {sexpr}")))
+    }
+}
+
+/// Convert an s-expression into an expression.
+pub fn to_expr(sexpr: &SExpr) -> Result<Expr,ReadError> {
+    match sexpr {
+        SExpr::Atom(s) if s == "true" => Ok(Expr::Boolean(true)),
+        SExpr::Atom(s) if s == "false" => Ok(Expr::Boolean(false)),
+        SExpr::Atom(s) if s.starts_with(|c:char| c.is_ascii_digit()) => {
+            Ok(Expr::Integer(to_numeral(s)?))
+        }
+        SExpr::Atom(s) => Ok(Expr::VarAccess(s.clone())),
+        SExpr::List(items) if items.len() == 3 && items[0].as_atom() == Some("as") => {
+            match (items[1].as_atom(),to_sort(&items[2])?) {
+                (Some("seq.empty"),sort@Sort::Seq(_)) => Ok(Expr::Empty(sort)),
+                _ => Err(error(&format!("invalid expression {sexpr}")))
+            }
+        }
+        SExpr::List(items) if items.len() == 3 && items[0].as_atom() == Some("forall") => {
+            let mut params = Vec::new();
+            for p in items[1].as_list().unwrap_or(&[]) {
+                match p.as_list() {
+                    Some([SExpr::Atom(n),s]) => params.push((n.clone(),to_sort(s)?)),
+                    _ => { return Err(error(&format!("invalid expression {sexpr}"))); }
+                }
+            }
+            Ok(Expr::Forall(params,Box::new(to_expr(&items[2])?)))
+        }
+        SExpr::List(items) if !items.is_empty() => {
+            let head = match items[0].as_atom() {
+                Some(h) => h,
+                None => { return Err(error(&format!("invalid expression {sexpr}"))); }
+            };
+            let args = items[1..].iter().map(to_expr).collect::<Result<Vec<_>,_>>()?;
+            match Op::from_symbol(head,args.len()) {
+                Some(op) => Ok(Expr::Operator(op,args)),
+                None => Ok(Expr::Apply(head.to_string(),args))
+            }
+        }
+        _ => Err(error(&format!("invalid expression {sexpr}")))
+    }
+}
+
+/// Convert an atom into a numeral.
+fn to_numeral(s: &str) -> Result<usize,ReadError> {
+    match s.parse::<usize>() {
+        Ok(i) => Ok(i),
+        Err(_) => Err(error(&format!("invalid numeral {s}")))
+    }
+}
+
+/// Construct an error arising from conversion (i.e. which has no
+/// meaningful offset).
+fn error(message: &str) -> ReadError {
+    ReadError::new(0,message)
+}
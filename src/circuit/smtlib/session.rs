@@ -0,0 +1,272 @@
+use std::fmt;
+use std::error::Error;
+use std::io::{self,BufRead,BufReader,Write};
+use std::path::Path;
+use std::process::{Child,ChildStdin,ChildStdout,Command as Process,Stdio};
+use std::sync::{Arc,Mutex,mpsc};
+use std::thread;
+use std::time::Duration;
+use super::ast::*;
+use super::{ReadError,SExpr,SmtLibReader,SmtLibWriter,to_model};
+
+// =============================================================================
+// Session Error
+// =============================================================================
+
+/// Identifies an error arising from communicating with a solver.
+#[derive(Debug)]
+pub enum SmtError {
+    /// Failure reading from (or writing to) the solver.
+    Io(io::Error),
+    /// A response from the solver was malformed.
+    Read(ReadError),
+    /// The solver responded with `(error ...)`.
+    Solver(String),
+    /// The solver responded with something other than expected (e.g.
+    /// ended its output before responding).
+    Unexpected(String),
+    /// The solver does not support the logic required.
+    Unsupported(String,String),
+    /// The solver failed to respond in time, and was killed.
+    Timeout
+}
+
+impl fmt::Display for SmtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SmtError::Io(e) => write!(f,"solver i/o error: {e}"),
+            SmtError::Read(e) => write!(f,"malformed solver response: {e}"),
+            SmtError::Solver(msg) => write!(f,"solver error: {msg}"),
+            SmtError::Unexpected(r) => write!(f,"unexpected solver response: {r}"),
+            SmtError::Unsupported(s,l) => write!(f,"solver `{s}` does not support logic {l}"),
+            SmtError::Timeout => write!(f,"solver failed to respond in time")
+        }
+    }
+}
+
+impl Error for SmtError {
+
+}
+
+impl From<io::Error> for SmtError {
+    fn from(e: io::Error) -> Self {
+        SmtError::Io(e)
+    }
+}
+
+impl From<ReadError> for SmtError {
+    fn from(e: ReadError) -> Self {
+        SmtError::Read(e)
+    }
+}
+
+// =============================================================================
+// Session
+// =============================================================================
+
+/// Response to a `check-sat` command.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum SatResult {
+    Sat,
+    Unsat,
+    Unknown
+}
+
+/// A session with a solver process running in interactive mode.
+pub type SmtProcess = SmtSession<BufReader<ChildStdout>,ChildStdin>;
+
+/// Represents an interactive session with an SMT solver, where
+/// commands are sent one at a time and the response to each is read
+/// before continuing.  To ensure every command produces a response,
+/// the session enables `:print-success` when it begins.  Typically,
+/// the solver is a child process communicating over pipes, though
+/// any reader and writer will do.
+pub struct SmtSession<R:BufRead,W:Write> {
+    /// Commands are written here
+    input: W,
+    /// Responses are read from here
+    output: R,
+    /// Output read from the solver but not yet parsed.
+    buffer: String,
+    /// The solver process (if applicable), which is shared with any
+    /// watchdog enforcing a timeout.
+    child: Option<Arc<Mutex<Child>>>
+}
+
+impl SmtSession<BufReader<ChildStdout>,ChildStdin> {
+    /// Start a solver process (e.g. `z3`), given the arguments needed
+    /// to put it into interactive mode (e.g. `--smt2 --in`).
+    pub fn spawn(path: &Path, args: &[&str]) -> Result<Self,SmtError> {
+        let mut child = Process::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        let mut session = SmtSession{input,output,buffer: String::new(),child: Some(Arc::new(Mutex::new(child)))};
+        session.begin()?;
+        Ok(session)
+    }
+}
+
+impl<R:BufRead,W:Write> SmtSession<R,W> {
+    /// Begin a session over an existing reader and writer.
+    pub fn new(output: R, input: W) -> Result<Self,SmtError> {
+        let mut session = SmtSession{input,output,buffer: String::new(),child: None};
+        session.begin()?;
+        Ok(session)
+    }
+
+    /// Send a command which is expected to succeed (i.e. which
+    /// produces no other response).
+    pub fn execute(&mut self, command: &Command) -> Result<(),SmtError> {
+        match self.send(command)? {
+            SExpr::Atom(s) if s == "success" => Ok(()),
+            r => Err(SmtError::Unexpected(r.to_string()))
+        }
+    }
+
+    pub fn push(&mut self, n: usize) -> Result<(),SmtError> {
+        self.execute(&Command::Push(n))
+    }
+
+    pub fn pop(&mut self, n: usize) -> Result<(),SmtError> {
+        self.execute(&Command::Pop(n))
+    }
+
+    pub fn assert(&mut self, expr: Expr) -> Result<(),SmtError> {
+        self.execute(&Command::Assert(expr))
+    }
+
+    pub fn check_sat(&mut self) -> Result<SatResult,SmtError> {
+        match self.send(&Command::CheckSat)? {
+            SExpr::Atom(s) if s == "sat" => Ok(SatResult::Sat),
+            SExpr::Atom(s) if s == "unsat" => Ok(SatResult::Unsat),
+            SExpr::Atom(s) if s == "unknown" => Ok(SatResult::Unknown),
+            r => Err(SmtError::Unexpected(r.to_string()))
+        }
+    }
+
+    /// As for `check_sat()`, except that a solver process which fails
+    /// to respond within a given time is killed.  In which case, the
+    /// session cannot be used further.  Without a process (e.g. when
+    /// testing), the timeout cannot be enforced.
+    pub fn check_sat_within(&mut self, timeout: Duration) -> Result<SatResult,SmtError> {
+        let child = match &self.child {
+            Some(child) => child.clone(),
+            None => { return self.check_sat(); }
+        };
+        let (cancel,cancelled) = mpsc::channel::<()>();
+        let watchdog = thread::spawn(move || {
+            // Kill solver unless cancelled in time
+            if cancelled.recv_timeout(timeout) == Err(mpsc::RecvTimeoutError::Timeout) {
+                let _ = child.lock().unwrap().kill();
+                true
+            } else {
+                false
+            }
+        });
+        let result = self.check_sat();
+        let _ = cancel.send(());
+        // NOTE: should the solver respond just as the watchdog fires,
+        // the response is discarded since the process has gone.
+        if watchdog.join().unwrap_or(false) {
+            Err(SmtError::Timeout)
+        } else {
+            result
+        }
+    }
+
+    /// Get a piece of information from the solver (e.g.
+    /// `:reason-unknown`), returning just its value.  This is `None`
+    /// if the solver responds `unsupported`.
+    pub fn get_info(&mut self, key: &str) -> Result<Option<SExpr>,SmtError> {
+        let r = self.send(&Command::GetInfo(key.to_string()))?;
+        if matches!(&r,SExpr::Atom(a) if a == "unsupported") {
+            return Ok(None);
+        }
+        match r.as_list() {
+            Some([SExpr::Atom(k),v]) if k == key => Ok(Some(v.clone())),
+            _ => Err(SmtError::Unexpected(r.to_string()))
+        }
+    }
+
+    /// Get the model following a `sat` response, as a list of
+    /// assignments from constants to values.
+    pub fn get_model(&mut self) -> Result<Vec<(String,SExpr)>,SmtError> {
+        let r = self.send(&Command::GetModel)?;
+        match to_model(&r) {
+            Some(model) => Ok(model),
+            None => Err(SmtError::Unexpected(r.to_string()))
+        }
+    }
+
+    /// Send a command to the solver, and read its response.  An
+    /// `(error ...)` response is reported as an error.
+    pub fn send(&mut self, command: &Command) -> Result<SExpr,SmtError> {
+        SmtLibWriter::new(&mut self.input).write(std::slice::from_ref(command))?;
+        self.input.flush()?;
+        //
+        let response = self.read_response()?;
+        match response.as_list() {
+            Some([SExpr::Atom(e),SExpr::String(msg)]) if e == "error" => {
+                Err(SmtError::Solver(msg.clone()))
+            }
+            _ => Ok(response)
+        }
+    }
+
+    // =========================================================================
+    // Internal
+    // =========================================================================
+
+    fn begin(&mut self) -> Result<(),SmtError> {
+        // Once enabled, this command itself responds with `success`
+        let cmd = Command::SetOption(":print-success".to_string(),"true".to_string());
+        SmtLibWriter::new(&mut self.input).write(&[cmd])?;
+        self.input.flush()?;
+        match self.read_response()? {
+            SExpr::Atom(s) if s == "success" => Ok(()),
+            r => Err(SmtError::Unexpected(r.to_string()))
+        }
+    }
+
+    /// Read the next complete s-expression from the solver, reading
+    /// further lines as necessary.
+    fn read_response(&mut self) -> Result<SExpr,SmtError> {
+        loop {
+            let mut reader = SmtLibReader::new(&self.buffer);
+            match reader.read_next() {
+                Ok(Some(sexpr)) => {
+                    self.buffer = self.buffer.chars().skip(reader.position()).collect();
+                    return Ok(sexpr);
+                }
+                Err(e) if !e.is_incomplete() => {
+                    return Err(SmtError::Read(e));
+                }
+                _ => {
+                    // Response not yet complete
+                    if self.output.read_line(&mut self.buffer)? == 0 {
+                        return Err(SmtError::Unexpected("end of output".to_string()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R:BufRead,W:Write> Drop for SmtSession<R,W> {
+    fn drop(&mut self) {
+        // Ask the solver to exit gracefully, though it may already
+        // have done so.
+        let _ = SmtLibWriter::new(&mut self.input).write(&[Command::Exit]);
+        let _ = self.input.flush();
+        if let Some(child) = self.child.take() {
+            if let Ok(mut child) = child.lock() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
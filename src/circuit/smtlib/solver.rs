@@ -1,58 +1,141 @@
-use std::io;
-use std::io::{BufWriter,Write};
 use std::path::Path;
-use std::process::{Command,Stdio};
-use crate::circuit;
+use std::time::Duration;
+use crate::circuit::{Limits,limit_exceeded};
 use super::ast;
-use super::SmtLibWriter;
+use super::{SatResult,SExpr,SmtError,SmtProcess,SolverProfile};
+
+/// Additional time given to a solver beyond its timeout, before it is
+/// killed.  This allows the solver to respond `unknown` by itself,
+/// which is preferable as the session can then continue.
+const KILL_GRACE : Duration = Duration::from_millis(500);
 
 pub enum SmtOutcome {
-    Sat,
+    /// Satisfiable, along with the model returned by the solver (if
+    /// any).
+    Sat(Vec<(String,SExpr)>),
     Unsat,
-    Maybe
+    Maybe,
+    /// The solver exceeded its time or resource limit.
+    Timeout
 }
 
 pub struct SmtSolver<'a> {
-    path: &'a Path
+    /// Determines how the solver is run.
+    profile: SolverProfile,
+    /// Path to the solver executable.
+    path: &'a Path,
+    /// Limits placed on each query.
+    limits: Limits
 }
 
 impl<'a> SmtSolver<'a> {
-    pub fn new(path: &'a Path) -> Self {
-        Self{path}
+    pub fn new(profile: SolverProfile, path: &'a Path) -> Self {
+        Self{profile,path,limits: Limits::default()}
     }
 
-    pub fn check(&self, commands: &[ast::Command]) -> Vec<SmtOutcome> {
-        let buffer = BufWriter::new(Vec::new());
-        let bytes = SmtLibWriter::new(Vec::new()).write(commands).unwrap();
-        let smt = String::from_utf8(bytes).unwrap();
-        // Pipe to Child
-        let mut child = Command::new(self.path)
-            .args(&["--smt2","--in"])
-            .stdout(Stdio::piped())
-            .stdin(Stdio::piped())
-            .spawn()
-            .expect("failed to start SMT solver");
-        // Grab the stdin handle.
-        let mut stdin = child.stdin.take().unwrap();
-        std::thread::spawn(move || {
-            println!("{smt}");
-            stdin.write_all(smt.as_bytes()).expect("Failed to write to stdin");
-            stdin.write_all(b"(check-sat)").expect("Failed to write to stdin");
-        });
-        // Get output back
-        let output = child.wait_with_output().expect("failed to read output");
-        let sout = String::from_utf8_lossy(&output.stdout);
-        //
+    /// Place limits on the time (or resources) spent on each query.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn profile(&self) -> SolverProfile {
+        self.profile
+    }
+
+    /// Start an interactive session with this solver, setting the
+    /// options required by its profile.
+    pub fn start(&self) -> Result<SmtProcess,SmtError> {
+        let mut session = SmtProcess::spawn(self.path,self.profile.args())?;
+        for (opt,val) in self.profile.options(&self.limits) {
+            session.execute(&ast::Command::SetOption(opt,val))?;
+        }
+        Ok(session)
+    }
+
+    /// Run a given set of commands through the solver, returning one
+    /// outcome for each `check-sat` command (in order).  Commands are
+    /// sent to a single solver session one at a time, and a model is
+    /// only requested following a `sat` response.  Should the solver
+    /// be killed for exceeding its timeout, it is restarted and the
+    /// commands sent so far replayed.
+    pub fn check(&self, commands: &[ast::Command]) -> Result<Vec<SmtOutcome>,SmtError> {
+        let logic = ast::logic(commands);
+        if !self.profile.supports(&logic) {
+            return Err(SmtError::Unsupported(self.profile.to_string(),logic));
+        }
+        let mut session = self.start()?;
+        session.execute(&ast::Command::SetLogic(logic.clone()))?;
+        // Commands sent so far (excluding queries)
+        let mut history = Vec::new();
         let mut outcomes = Vec::new();
         //
-        for l in sout.lines() {
-            match l {
-                "sat" => outcomes.push(SmtOutcome::Sat),
-                "unsat" => outcomes.push(SmtOutcome::Unsat),
-                _ => outcomes.push(SmtOutcome::Maybe)
+        for cmd in commands {
+            match cmd {
+                ast::Command::CheckSat => {
+                    let outcome = match self.check_sat(&mut session) {
+                        Err(SmtError::Timeout) => {
+                            session = self.restart(&logic,&history)?;
+                            SmtOutcome::Timeout
+                        }
+                        r => r?
+                    };
+                    outcomes.push(outcome);
+                }
+                ast::Command::GetModel => {
+                    if let Some(SmtOutcome::Sat(m)) = outcomes.last_mut() {
+                        *m = session.get_model()?;
+                    }
+                }
+                ast::Command::Exit => { break; }
+                _ => {
+                    session.execute(cmd)?;
+                    history.push(cmd);
+                }
             }
         }
-        //
-        outcomes
+        Ok(outcomes)
+    }
+
+    // =========================================================================
+    // Internal
+    // =========================================================================
+
+    /// Check satisfiability of the current assertions, subject to any
+    /// limits.  An `unknown` response is reported as a timeout when
+    /// the solver indicates that a limit was exceeded.
+    fn check_sat(&self, session: &mut SmtProcess) -> Result<SmtOutcome,SmtError> {
+        let result = match self.limits.timeout {
+            Some(ms) => session.check_sat_within(Duration::from_millis(ms) + KILL_GRACE)?,
+            None => session.check_sat()?
+        };
+        match result {
+            SatResult::Sat => Ok(SmtOutcome::Sat(Vec::new())),
+            SatResult::Unsat => Ok(SmtOutcome::Unsat),
+            SatResult::Unknown if !self.limits.is_none() => {
+                // Not all solvers support this, so failure is ignored
+                let reason = match session.get_info(":reason-unknown") {
+                    Ok(Some(r)) => r.to_string(),
+                    Ok(None)|Err(SmtError::Solver(_)) => String::new(),
+                    Err(e) => { return Err(e); }
+                };
+                if limit_exceeded(&reason) {
+                    Ok(SmtOutcome::Timeout)
+                } else {
+                    Ok(SmtOutcome::Maybe)
+                }
+            }
+            SatResult::Unknown => Ok(SmtOutcome::Maybe)
+        }
+    }
+
+    /// Start a fresh session, replaying a given set of commands.
+    fn restart(&self, logic: &str, history: &[&ast::Command]) -> Result<SmtProcess,SmtError> {
+        let mut session = self.start()?;
+        session.execute(&ast::Command::SetLogic(logic.to_string()))?;
+        for cmd in history {
+            session.execute(cmd)?;
+        }
+        Ok(session)
     }
 }
@@ -1,22 +1,38 @@
+use std::error::Error;
 use z3::ast::{Ast,Dynamic};
 use z3::*;
-use super::{Circuit,Any,Bool,Function,Int,Outcome,Type};
+use super::{Circuit,Any,Array,Bool,Function,Int,Limits,Model,Outcome,SmtLibReader,Tuple,Type,VcDescriptor,limit_exceeded,to_value};
 
 type Z3Any<'a> = z3::ast::Dynamic<'a>;
 type Z3Bool<'a> = z3::ast::Bool<'a>;
 type Z3Int<'a> = z3::ast::Int<'a>;
 type Z3Type<'a> = z3::Sort<'a>;
-type Z3Func<'a> = z3::FuncDecl<'a>;
+type Z3Func<'a> = z3::RecFuncDecl<'a>;
 
 pub struct Z3Circuit<'a> {
     context: &'a Context,
     /// Set of asserted verification conditions.
-    conditions: Vec<Z3Bool<'a>>
+    conditions: Vec<(VcDescriptor,Z3Bool<'a>)>,
+    /// Set of declared constants, used for extracting models.
+    declared: Vec<(String,Z3Any<'a>)>,
+    /// Limits placed on each query.
+    limits: Limits,
+    /// Constraints which hold for every query (e.g. defining array
+    /// generators).
+    axioms: Vec<Z3Bool<'a>>,
+    /// Datatypes constructed for tuples, indexed by their field sorts.
+    tuples: Vec<(Vec<Z3Type<'a>>,DatatypeSort<'a>)>
 }
 
 impl<'a> Z3Circuit<'a> {
     pub fn new(context: &'a Context) -> Self {
-        Self{context, conditions: Vec::new()}
+        Self{context, conditions: Vec::new(), declared: Vec::new(), limits: Limits::default(), axioms: Vec::new(), tuples: Vec::new()}
+    }
+
+    /// Place limits on the time (or resources) spent on each query.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
     }
 
     pub fn len(&self) -> usize {
@@ -24,20 +40,89 @@ impl<'a> Z3Circuit<'a> {
     }
 
     pub fn check(&self, ith: usize) -> Outcome {
-        let vc = &self.conditions[ith];
+        let (_,vc) = &self.conditions[ith];
         let solver = Solver::new(&self.context);
+        // Apply limits (if any)
+        let mut params = Params::new(self.context);
+        if let Some(ms) = self.limits.timeout {
+            params.set_u32("timeout",u32::try_from(ms).unwrap_or(u32::MAX));
+        }
+        if let Some(n) = self.limits.rlimit {
+            params.set_u32("rlimit",u32::try_from(n).unwrap_or(u32::MAX));
+        }
+        solver.set_params(&params);
+        for axiom in &self.axioms {
+            solver.assert(axiom);
+        }
         // Assert it
         solver.assert(&vc.not());
         // Check it
         match solver.check() {
              SatResult::Unsat => Outcome::Valid,
-             SatResult::Sat => Outcome::Invalid,
-             SatResult::Unknown => Outcome::Unknown
+             SatResult::Sat => Outcome::Invalid(self.to_model(&solver)),
+             SatResult::Unknown => {
+                 match solver.get_reason_unknown() {
+                     Some(r) if limit_exceeded(&r) => Outcome::Timeout,
+                     _ => Outcome::Unknown
+                 }
+             }
         }
     }
 
-    pub fn discharge(&mut self, condition: Z3Bool<'a>) {
-        self.conditions.push(condition);
+    /// Extract a counterexample from a solver which has just returned
+    /// `sat`.  Constants left unconstrained by the model are omitted.
+    /// Values are rendered from their SMT-LIB form, exactly as for the
+    /// SMT-LIB backend (e.g. `(seq.++ (seq.unit 1) (seq.unit 2))`
+    /// becomes `[1, 2]`).
+    fn to_model(&self, solver: &Solver<'a>) -> Model {
+        let mut model = Model::new();
+        if let Some(m) = solver.get_model() {
+            for (name,var) in &self.declared {
+                let value = match m.eval(var,false) {
+                    Some(v) if &v != var => v,
+                    _ => { continue; }
+                };
+                let text = value.to_string();
+                if let Ok(Some(sexpr)) = SmtLibReader::new(&text).read_next() {
+                    model.assign(name,&to_value(&sexpr));
+                }
+            }
+        }
+        model
+    }
+
+    /// Record a declared constant, for use when extracting models.
+    /// Since functions are verified independently, their parameters
+    /// may share names.  Z3 identifies constants by name and sort,
+    /// hence a redeclaration with the same sort is the same constant,
+    /// and is recorded only once.
+    fn record(&mut self, name: &str, var: Z3Any<'a>) {
+        if !self.declared.iter().any(|(_,v)| *v == var) {
+            self.declared.push((name.to_string(),var));
+        }
+    }
+
+    pub fn discharge(&mut self, descriptor: VcDescriptor, condition: Z3Bool<'a>) {
+        self.conditions.push((descriptor,condition));
+    }
+
+    /// Get the datatype for tuples with the given field sorts,
+    /// constructing it if necessary.
+    fn tuple_datatype(&mut self, fields: &[Z3Type<'a>]) -> &DatatypeSort<'a> {
+        if let Some(i) = self.tuples.iter().position(|(fs,_)| fs.as_slice() == fields) {
+            return &self.tuples[i].1;
+        }
+        // NOTE: `!` cannot appear in source-level names
+        let name = format!("Tuple!{}",self.tuples.len());
+        let selectors : Vec<String> = (0..fields.len()).map(|i| format!("{name}.{i}")).collect();
+        let accessors = selectors.iter().zip(fields).map(|(n,f)| {
+            (n.as_str(),DatatypeAccessor::Sort(f.clone()))
+        }).collect();
+        let datatype = DatatypeBuilder::new(self.context,name.as_str())
+            .variant(&name.to_lowercase(),accessors)
+            .finish();
+        self.tuples.push((fields.to_vec(),datatype));
+        &self.tuples[self.tuples.len()-1].1
     }
 }
 
@@ -48,6 +133,8 @@ impl<'a> Circuit for Z3Circuit<'a> {
     type Int = Z3Int<'a>;
     type Type = Z3Type<'a>;
     type Function = Z3Func<'a>;
+    type Array = Z3Any<'a>;
+    type Tuple = Z3Any<'a>;
 
     fn from_bool(&self, val: bool) -> Self::Bool {
         Z3Bool::from_bool(&self.context,val)
@@ -57,20 +144,51 @@ impl<'a> Circuit for Z3Circuit<'a> {
         Z3Int::from_u64(&self.context,val as u64)
     }
 
-    fn declare_bool(&self, name: &str) -> Self::Bool {
-        Z3Bool::new_const(self.context,name)
+    fn declare_bool(&mut self, name: &str) -> Self::Bool {
+        let var = Z3Bool::new_const(self.context,name);
+        self.record(name,Z3Any::from_ast(&var));
+        var
     }
 
-    fn declare_int(&self, name: &str) -> Self::Int {
-        Z3Int::new_const(self.context,name)
+    fn declare_int(&mut self, name: &str) -> Self::Int {
+        let var = Z3Int::new_const(self.context,name);
+        self.record(name,Z3Any::from_ast(&var));
+        var
     }
 
-    fn declare_fn(&self, name: &str, params: &[Self::Type], rets: &[Self::Type]) -> Self::Function {
-        // Sanity check for now
-        assert!(rets.len() <= 1);
-        let params : Vec<&Sort<'a>> = params.iter().map(|p| p).collect();
+    fn declare_array(&mut self, name: &str, element: &Self::Type) -> Self::Array {
+        let sort = self.array_type(element);
+        let var = Z3Any::new_const(self.context,name,&sort);
+        self.record(name,var.clone());
+        var
+    }
+
+    fn declare_tuple(&mut self, name: &str, fields: &[Self::Type]) -> Self::Tuple {
+        let sort = self.tuple_type(fields);
+        let var = Z3Any::new_const(self.context,name,&sort);
+        self.record(name,var.clone());
+        var
+    }
+
+    fn declare_fn(&mut self, name: &str, params: &[Self::Type], rets: &[Self::Type]) -> Self::Function {
+        assert!(!rets.is_empty());
+        // Multiple returns are returned as a tuple
+        let ret = match rets {
+            [r] => r.clone(),
+            _ => self.tuple_type(rets)
+        };
+        let params : Vec<&Sort<'a>> = params.iter().collect();
         //
-        Z3Func::new(self.context,name.to_string(),&params,&rets[0])
+        Z3Func::new(self.context,name.to_string(),&params,&ret)
+    }
+
+    fn declare_param(&mut self, name: &str, typ: &Self::Type) -> Self::Term {
+        Z3Any::fresh_const(self.context,name,typ)
+    }
+
+    fn define_fn(&mut self, function: &Self::Function, params: &[(Self::Term,Self::Type)], body: &Self::Term) {
+        let args : Vec<&dyn Ast<'a>> = params.iter().map(|(p,_)| p as &dyn Ast<'a>).collect();
+        function.add_def(&args,body);
     }
 
     fn bool_type(&self) -> Self::Type {
@@ -81,16 +199,65 @@ impl<'a> Circuit for Z3Circuit<'a> {
         Sort::int(self.context)
     }
 
-    fn assert(&mut self, condition: Self::Bool) {
-        Z3Circuit::discharge(self,condition);
+    fn array_type(&self, element: &Self::Type) -> Self::Type {
+        seq_sort(self.context,element)
+    }
+
+    fn tuple_type(&mut self, fields: &[Self::Type]) -> Self::Type {
+        self.tuple_datatype(fields).sort.clone()
+    }
+
+    fn tuple_from(&mut self, fields: &[Self::Type], items: &[Self::Term]) -> Self::Tuple {
+        let datatype = self.tuple_datatype(fields);
+        let args : Vec<&dyn Ast<'a>> = items.iter().map(|i| i as &dyn Ast<'a>).collect();
+        datatype.variants[0].constructor.apply(&args)
+    }
+
+    fn array_from(&self, element: &Self::Type, items: &[Self::Term]) -> Self::Array {
+        let ctx = self.context.get_z3_context();
+        // NOTE: units are wrapped to ensure they are reference counted.
+        let units : Vec<Z3Any<'a>> = items.iter().map(|i| {
+            unsafe { Z3Any::wrap(self.context,z3_sys::Z3_mk_seq_unit(ctx,i.get_z3_ast())) }
+        }).collect();
+        let asts : Vec<z3_sys::Z3_ast> = units.iter().map(|u| u.get_z3_ast()).collect();
+        match units.len() {
+            0 => unsafe {
+                Z3Any::wrap(self.context,z3_sys::Z3_mk_seq_empty(ctx,self.array_type(element).get_z3_sort()))
+            }
+            1 => units[0].clone(),
+            n => unsafe {
+                Z3Any::wrap(self.context,z3_sys::Z3_mk_seq_concat(ctx,n as u32,asts.as_ptr()))
+            }
+        }
+    }
+
+    /// As for sequences in SMT-LIB, an array generator `[e;n]` is
+    /// represented by a fresh constant constrained by an axiom.
+    fn array_generator(&mut self, element: &Self::Type, item: &Self::Term, len: &Self::Int) -> Self::Array {
+        let sort = self.array_type(element);
+        let g = Z3Any::fresh_const(self.context,"array",&sort);
+        let g_len = Array::length(&g);
+        let zero = Z3Int::from_u64(self.context,0);
+        let n = len.ge(&zero).ite(len,&zero);
+        // Every element within bounds is the item
+        let i = Z3Int::fresh_const(self.context,"i");
+        let in_bounds = Z3Bool::and(self.context,&[&zero.le(&i),&i.lt(&g_len)]);
+        let body = in_bounds.implies(&Array::get(&g,&i)._eq(item));
+        let elements = z3::ast::forall_const(self.context,&[&i],&[],&body);
+        self.axioms.push(Z3Bool::and(self.context,&[&g_len._eq(&n),&elements]));
+        g
+    }
+
+    fn assert(&mut self, descriptor: VcDescriptor, condition: Self::Bool) {
+        Z3Circuit::discharge(self,descriptor,condition);
     }
 
-    fn check(&self) -> Vec<Outcome> {
+    fn check(&self) -> Result<Vec<(VcDescriptor,Outcome)>,Box<dyn Error>> {
         let mut outcomes = Vec::new();
         for i in 0..self.conditions.len() {
-            outcomes.push(self.check(i));
+            outcomes.push((self.conditions[i].0,self.check(i)));
         }
-        outcomes
+        Ok(outcomes)
     }
 }
 
@@ -155,7 +322,10 @@ impl<'a> Int for Z3Int<'a> {
         Z3Any::from_ast(self)
     }
     // Comparators
-    fn non_zero(&self) -> Self::Bool { todo!() }
+    fn non_zero(&self) -> Self::Bool {
+        let zero = Z3Int::from_u64(self.get_ctx(),0);
+        self._eq(&zero).not()
+    }
     fn lt(&self, other: &Self) -> Self::Bool { self.lt(other) }
     fn lteq(&self, other: &Self) -> Self::Bool { self.le(other) }
     fn gt(&self, other: &Self) -> Self::Bool { self.gt(other) }
@@ -164,9 +334,98 @@ impl<'a> Int for Z3Int<'a> {
     fn neg(&self) -> Self { std::ops::Neg::neg(self) }
     fn add(&self, other: &Self) -> Self { std::ops::Add::add(self,other) }
     fn sub(&self, other: &Self) -> Self { std::ops::Sub::sub(self,other) }
-    fn div(&self, other: &Self) -> Self { std::ops::Div::div(self,other) }
+    fn div(&self, other: &Self) -> Self {
+        truncate(self,other,|x,y| std::ops::Div::div(x,y))
+    }
     fn mul(&self, other: &Self) -> Self { std::ops::Mul::mul(self,other) }
-    fn rem(&self, other: &Self) -> Self { std::ops::Rem::rem(self,other) }
+    fn rem(&self, other: &Self) -> Self {
+        truncate(self,other,|x,y| x.modulo(y))
+    }
+}
+
+/// Apply a Euclidean operator (i.e. Z3's `div` or `mod`) such that it
+/// truncates towards zero instead.  These agree for a non-negative
+/// dividend, and otherwise `x op y == -((-x) op y)`.
+fn truncate<'a>(x: &Z3Int<'a>, y: &Z3Int<'a>, op: impl Fn(&Z3Int<'a>,&Z3Int<'a>) -> Z3Int<'a>) -> Z3Int<'a> {
+    let zero = Z3Int::from_u64(x.get_ctx(),0);
+    let neg = std::ops::Neg::neg(op(&std::ops::Neg::neg(x),y));
+    x.ge(&zero).ite(&op(x,y),&neg)
+}
+
+// =============================================================================
+// Z3 Array
+// =============================================================================
+
+// NOTE: the z3 crate does not (yet) support sequences, hence these
+// are constructed directly using the underlying API.
+
+impl<'a> Array for Z3Any<'a> {
+    type Any = Z3Any<'a>;
+    type Int = Z3Int<'a>;
+
+    fn from_any(any: &Self::Any) -> Self {
+        any.clone()
+    }
+    fn to_any(&self) -> Self::Any {
+        self.clone()
+    }
+    fn length(&self) -> Self::Int {
+        let ctx = self.get_ctx();
+        unsafe {
+            Z3Int::wrap(ctx,z3_sys::Z3_mk_seq_length(ctx.get_z3_context(),self.get_z3_ast()))
+        }
+    }
+    fn get(&self, index: &Self::Int) -> Self::Any {
+        let ctx = self.get_ctx();
+        unsafe {
+            Z3Any::wrap(ctx,z3_sys::Z3_mk_seq_nth(ctx.get_z3_context(),self.get_z3_ast(),index.get_z3_ast()))
+        }
+    }
+    fn slice(&self, start: &Self::Int, end: &Self::Int) -> Self {
+        let ctx = self.get_ctx();
+        let len = std::ops::Sub::sub(end,start);
+        unsafe {
+            Z3Any::wrap(ctx,z3_sys::Z3_mk_seq_extract(ctx.get_z3_context(),self.get_z3_ast(),start.get_z3_ast(),len.get_z3_ast()))
+        }
+    }
+}
+
+/// Construct the sort of sequences with elements of a given sort.
+fn seq_sort<'a>(context: &'a Context, element: &Z3Type<'a>) -> Z3Type<'a> {
+    unsafe {
+        let ctx = context.get_z3_context();
+        let sort = z3_sys::Z3_mk_seq_sort(ctx,element.get_z3_sort());
+        // Sorts cannot be wrapped directly, hence go via a constant.
+        let symbol = z3_sys::Z3_mk_int_symbol(ctx,0);
+        Z3Any::wrap(context,z3_sys::Z3_mk_const(ctx,symbol,sort)).get_sort()
+    }
+}
+
+// =============================================================================
+// Z3 Tuple
+// =============================================================================
+
+impl<'a> Tuple for Z3Any<'a> {
+    type Any = Z3Any<'a>;
+
+    fn from_any(any: &Self::Any) -> Self {
+        any.clone()
+    }
+    fn to_any(&self) -> Self::Any {
+        self.clone()
+    }
+    fn get(&self, index: usize, _size: usize) -> Self::Any {
+        // NOTE: the accessor is recovered from the sort of this tuple,
+        // which is always a datatype with exactly one constructor.
+        let ctx = self.get_ctx();
+        unsafe {
+            let c = ctx.get_z3_context();
+            let sort = z3_sys::Z3_get_sort(c,self.get_z3_ast());
+            let accessor = z3_sys::Z3_get_datatype_sort_constructor_accessor(c,sort,0,index as u32);
+            let args = [self.get_z3_ast()];
+            Z3Any::wrap(ctx,z3_sys::Z3_mk_app(c,accessor,1,args.as_ptr()))
+        }
+    }
 }
 
 // =============================================================================
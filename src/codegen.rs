@@ -1,18 +1,22 @@
-use crate::{BinOp,Function,SyntacticHeap,Term};
+use crate::{BinOp,Function,SyntacticHeap,Term,Type,TypeChecker,Typing,UnaryOp};
 
 /// Simplest possible code generator
 pub struct RustPrinter<'a> {
     heap: &'a SyntacticHeap,
+    /// Types determined for the program being printed.  These are
+    /// needed since Rust has no implicit conversions between integer
+    /// types (e.g. from `usize` to `isize`).
+    typing: &'a Typing,
     out: String,
     indent: usize
 }
 
 impl<'a> RustPrinter<'a> {
     /// Create a new printer
-    pub fn new(heap: &'a SyntacticHeap) -> Self {
+    pub fn new(heap: &'a SyntacticHeap, typing: &'a Typing) -> Self {
         let out = Self::preamble().to_string();
         let indent = 0;
-        Self{heap, out, indent}
+        Self{heap, typing, out, indent}
     }
 
     pub fn preamble() -> &'static str {
@@ -25,7 +29,7 @@ impl<'a> RustPrinter<'a> {
 
     pub fn writeln(&mut self, text: &str) {
         self.write(text);
-        self.out.push_str("\n");
+        self.out.push('\n');
     }
 
     pub fn indent(&mut self, text: &str) {
@@ -51,21 +55,24 @@ impl<'a> RustPrinter<'a> {
             // Statements
             Term::Assert(src) => self.gen_assert(*src),
             Term::Assume(src) => self.gen_assert(*src),
-            Term::Block(terms) => self.gen_block(terms),
+            Term::Block(terms) => self.gen_block(terms,self.typing.type_of(index)),
+            Term::Let(var,src) => self.gen_let(var,*src),
+            Term::LetTuple(vars,src) => self.gen_let_tuple(vars,*src),
             // Expressions
             Term::ArrayAccess{src,index} => self.gen_array_access(*src,*index),
             Term::ArrayGenerator(item,len) => self.gen_array_generator(*item,*len),
             Term::ArraySlice{src,start,end} => self.gen_array_slice(*src,*start,*end),
             Term::ArrayLength(src) => self.gen_array_length(*src),
-            Term::ArrayConstructor(vs) => self.gen_array_constructor(vs),
+            Term::ArrayConstructor(vs) => self.gen_array_constructor(index,vs),
             Term::Binary(bop,l,r) => self.gen_binary(*bop,*l,*r),
             Term::BoolLiteral(v) => self.gen_bool_literal(*v),
             Term::Braced(v) => self.gen_braced(*v),
-            Term::IfElse{cond,tt,ff} => self.gen_if(*cond,*tt,*ff),
+            Term::IfElse{cond,tt,ff} => self.gen_if(*cond,*tt,*ff,self.typing.type_of(index)),
             Term::IntLiteral(v) => self.gen_int_literal(*v),
             Term::StaticInvoke(name,args) => self.gen_static_invoke(name,args),
             Term::TupleAccess(src,index) => self.gen_tuple_access(*src,*index),
             Term::TupleConstructor(vs) => self.gen_tuple_constructor(vs),
+            Term::Unary(uop,src) => self.gen_unary(*uop,*src),
             Term::VarAccess(v) => self.gen_var_access(v),
             // Types
             Term::ArrayType(src) => self.gen_array_type(*src),
@@ -75,6 +82,80 @@ impl<'a> RustPrinter<'a> {
         }
     }
 
+    /// Print out an expression whose value flows into a context of a
+    /// given type.  Since every `uint` is an `int`, this may differ
+    /// from the type of the expression itself and, in such case, a
+    /// cast is inserted (e.g. `x as isize`).  Likewise, a signed
+    /// integer used as an index is cast to `usize`.
+    fn generate_as(&mut self, index: usize, target: &Type) {
+        let typing = self.typing;
+        let t = typing.type_of(index);
+        if t == target {
+            return self.generate(index);
+        }
+        match (self.heap.get(index),target) {
+            // Rust infers the type of integer literals
+            (Term::IntLiteral(_),_) => self.generate(index),
+            // Push the conversion inwards where possible
+            (Term::Block(terms),_) => self.gen_block(terms,target),
+            (Term::Braced(e),_) => {
+                self.write("(");
+                self.generate_as(*e,target);
+                self.write(")");
+            }
+            (Term::IfElse{cond,tt,ff},_) => self.gen_if(*cond,*tt,*ff,target),
+            (Term::ArrayConstructor(vs),Type::Array(e)) => self.gen_array_constructor_as(vs,e),
+            (Term::ArrayGenerator(item,len),Type::Array(e)) => self.gen_array_generator_as(*item,*len,e),
+            (Term::TupleConstructor(vs),Type::Tuple(ts)) => {
+                self.write("(");
+                for (i,(v,t)) in vs.iter().zip(ts).enumerate() {
+                    if i != 0 { self.write(","); }
+                    self.generate_as(*v,t);
+                }
+                self.write(")");
+            }
+            // Otherwise, convert the value produced
+            (term,_) => {
+                let mut expr = self.capture(index);
+                if matches!(term,Term::Binary(..)) {
+                    expr = format!("({expr})");
+                }
+                self.write(&Self::convert(expr,t,target));
+            }
+        }
+    }
+
+    /// Print out a given term into a separate string, rather than the
+    /// output.
+    fn capture(&mut self, index: usize) -> String {
+        let out = std::mem::take(&mut self.out);
+        self.generate(index);
+        std::mem::replace(&mut self.out, out)
+    }
+
+    /// Convert a Rust expression of one type into another.  Arrays and
+    /// tuples are converted element-wise.  Observe an array whose
+    /// elements are `Bottom` must be empty.
+    fn convert(expr: String, from: &Type, to: &Type) -> String {
+        match (from,to) {
+            _ if from == to => expr,
+            (Type::Int(_),Type::Int(true)) => format!("({expr} as isize)"),
+            (Type::Int(_),Type::Int(false)) => format!("({expr} as usize)"),
+            (Type::Array(e1),Type::Array(_)) if **e1 == Type::Bottom => "Vec::new()".to_string(),
+            (Type::Array(e1),Type::Array(e2)) => {
+                let v = Self::convert("v".to_string(),e1,e2);
+                format!("{expr}.iter().cloned().map(|v| {v}).collect::<Vec<_>>()")
+            }
+            (Type::Tuple(ts1),Type::Tuple(ts2)) => {
+                let vs : Vec<String> = ts1.iter().zip(ts2).enumerate().map(|(i,(t1,t2))| {
+                    Self::convert(format!("t.{i}"),t1,t2)
+                }).collect();
+                format!("{{ let t = {expr}; ({}) }}",vs.join(","))
+            }
+            _ => expr
+        }
+    }
+
     // ===============================================================
     // Declarations
     // ===============================================================
@@ -91,19 +172,27 @@ impl<'a> RustPrinter<'a> {
             self.generate(*t);
         }
         self.write(")");
-	if !fun.rets.is_empty() {
-            self.write(" -> ");
-	    self.write("(");
-            for i in 0..fun.rets.len() {
-		let (t,v) = &fun.rets[i];
-		if i != 0 { self.write(", "); }
-		self.write(v);
-		self.write(": ");
-		self.generate(*t);
+        // Rust has no named returns.  Instead, a function with
+        // multiple returns returns a tuple (as does its body).
+        match fun.rets.len() {
+            0 => {}
+            1 => {
+                self.write(" -> ");
+                self.generate(fun.rets[0].0);
+            }
+            _ => {
+                self.write(" -> (");
+                for (i,(t,_)) in fun.rets.iter().enumerate() {
+                    if i != 0 { self.write(", "); }
+                    self.generate(*t);
+                }
+                self.write(")");
             }
-            self.write(")");	    
         }
-        self.generate(fun.body);
+        match TypeChecker::return_type(self.heap,fun) {
+            Type::Void => self.generate(fun.body),
+            ret => self.generate_as(fun.body,&ret)
+        }
         self.writeln("");
     }
 
@@ -117,19 +206,39 @@ impl<'a> RustPrinter<'a> {
         self.write(")");
     }
 
-    fn gen_block(&mut self, terms: &Vec<usize>) {
+    /// Print out a block whose value (i.e. trailing expression) flows
+    /// into a context of a given type.
+    fn gen_block(&mut self, terms: &[usize], target: &Type) {
         self.writeln(" {");
         self.indent += 1;
         self.indent("");
-        for i in 0..terms.len() {
+        for (i,t) in terms.iter().enumerate() {
             if i > 0 { self.writeln("; "); self.indent(""); }
-            self.generate(terms[i]);
+            if i + 1 == terms.len() {
+                self.generate_as(*t,target);
+            } else {
+                self.generate(*t);
+            }
         }
         self.writeln("");
         self.indent -= 1;
         self.indent("}");
     }
 
+    fn gen_let(&mut self, var: &str, src: usize) {
+        self.write("let ");
+        self.write(var);
+        self.write(" = ");
+        self.generate(src);
+    }
+
+    fn gen_let_tuple(&mut self, vars: &[String], src: usize) {
+        self.write("let (");
+        self.write(&vars.join(", "));
+        self.write(") = ");
+        self.generate(src);
+    }
+
     // ===============================================================
     // Expressions
     // ===============================================================
@@ -137,26 +246,45 @@ impl<'a> RustPrinter<'a> {
     fn gen_array_access(&mut self, src: usize, index: usize) {
         self.generate(src);
         self.write("[");
-        self.generate(index);
+        self.generate_as(index,&Type::Int(false));
         self.write("]");
     }
 
-    fn gen_array_constructor(&mut self, terms: &Vec<usize>) {
+    fn gen_array_constructor(&mut self, index: usize, terms: &[usize]) {
+        match self.typing.type_of(index) {
+            // An empty array with no context from which to determine
+            // its type (e.g. `[] == []`) cannot be inferred by Rust.
+            Type::Array(e) if **e == Type::Bottom => self.write("Vec::<()>::new()"),
+            Type::Array(e) => self.gen_array_constructor_as(terms,e),
+            t => unreachable!("not an array {t:?}")
+        }
+    }
+
+    /// Print out an array constructor whose elements flow into a
+    /// context of a given type.
+    fn gen_array_constructor_as(&mut self, terms: &[usize], target: &Type) {
         self.write("vec![");
-        for i in 0..terms.len() {
+        for (i,t) in terms.iter().enumerate() {
             if i != 0 {
                 self.write(",");
             }
-            self.generate(terms[i]);
+            self.generate_as(*t,target);
         }
         self.write("]");
     }
 
     fn gen_array_generator(&mut self, item: usize, len: usize) {
+        let typing = self.typing;
+        self.gen_array_generator_as(item,len,typing.type_of(item));
+    }
+
+    /// Print out an array generator whose item flows into a context
+    /// of a given type.
+    fn gen_array_generator_as(&mut self, item: usize, len: usize, target: &Type) {
         self.write("vec![");
-        self.generate(item);
+        self.generate_as(item,target);
         self.write(";");
-        self.generate(len);
+        self.generate_as(len,&Type::Int(false));
         self.write("]");
     }
 
@@ -168,16 +296,40 @@ impl<'a> RustPrinter<'a> {
     fn gen_array_slice(&mut self, src: usize, start: usize, end: usize) {
         self.generate(src);
         self.write("[");
-        self.generate(start);
+        self.generate_as(start,&Type::Int(false));
         self.write("..");
-        self.generate(end);
+        self.generate_as(end,&Type::Int(false));
         self.write("].to_vec()");
     }
 
     fn gen_binary(&mut self, bop: BinOp, lhs: usize, rhs: usize) {
-        self.generate(lhs);
-        self.write(Self::bop_to_str(bop));
-        self.generate(rhs);
+        match bop {
+            BinOp::LogicalImplies => {
+                // Rust has no implication, so use `!lhs || rhs`
+                self.write("(!(");
+                self.generate(lhs);
+                self.write(")||(");
+                self.generate(rhs);
+                self.write("))");
+            }
+            BinOp::LogicalIff => {
+                self.write("((");
+                self.generate(lhs);
+                self.write(")==(");
+                self.generate(rhs);
+                self.write("))");
+            }
+            _ => {
+                // Operands are converted to a common type (e.g. `uint`
+                // and `int` operands are both treated as `int`).
+                let typing = self.typing;
+                let (l,r) = (typing.type_of(lhs),typing.type_of(rhs));
+                let t = l.join(r).unwrap();
+                self.generate_as(lhs,&t);
+                self.write(Self::bop_to_str(bop));
+                self.generate_as(rhs,&t);
+            }
+        }
     }
 
     fn gen_braced(&mut self, src: usize) {
@@ -186,26 +338,46 @@ impl<'a> RustPrinter<'a> {
         self.write(")");
     }
 
-    fn gen_if(&mut self, cond: usize, tt: usize, ff: usize) {
+    /// Print out a conditional whose value flows into a context of a
+    /// given type.
+    fn gen_if(&mut self, cond: usize, tt: usize, ff: usize, target: &Type) {
         self.write("if ");
         self.generate(cond);
-        self.generate(tt);
+        self.generate_as(tt,target);
         self.write(" else");
-        self.generate(ff);
+        self.generate_as(ff,target);
+    }
+
+    fn gen_unary(&mut self, uop: UnaryOp, src: usize) {
+        match uop {
+            UnaryOp::Negate => {
+                // Rust cannot negate unsigned integers, so use `0 - src`
+                // instead (which is well-defined when `src == 0`).
+                self.write("(0-");
+                self.generate(src);
+                self.write(")");
+            }
+            UnaryOp::LogicalNot => {
+                self.write("!");
+                self.generate(src);
+            }
+        }
     }
 
     fn gen_var_access(&mut self, var: &str) {
         self.write(var)
     }
 
-    fn gen_static_invoke(&mut self, name: &String, args: &Vec<usize>) {
+    fn gen_static_invoke(&mut self, name: &str, args: &[usize]) {
+        let typing = self.typing;
+        let (params,_) = typing.signature(name).unwrap();
         self.write(name);
         self.write("(");
-        for i in 0..args.len() {
+        for (i,(arg,param)) in args.iter().zip(params).enumerate() {
             if i != 0 {
                 self.write(",");
             }
-            self.generate(args[i]);
+            self.generate_as(*arg,param);
         }
         self.write(")");
     }
@@ -216,7 +388,7 @@ impl<'a> RustPrinter<'a> {
         self.write(&index.to_string());
     }
 
-    fn gen_tuple_constructor(&mut self, terms: &Vec<usize>) {
+    fn gen_tuple_constructor(&mut self, terms: &[usize]) {
         self.write("(");
         for (i,_) in terms.iter().enumerate() {
             if i != 0 {
@@ -265,7 +437,7 @@ impl<'a> RustPrinter<'a> {
         }
     }
 
-    fn gen_tuple_type(&mut self, types: &Vec<usize>) {
+    fn gen_tuple_type(&mut self, types: &[usize]) {
         self.write("(");
         for (i,_) in types.iter().enumerate() {
             if i != 0 { self.write(","); }
@@ -293,8 +465,9 @@ impl<'a> RustPrinter<'a> {
             BinOp::GreaterThanOrEquals => ">=",
             // Logical
             BinOp::LogicalAnd => "&&",
-            BinOp::LogicalImplies => todo!(),
-            BinOp::LogicalOr => "||"
+            BinOp::LogicalOr => "||",
+            // Handled by gen_binary
+            BinOp::LogicalIff|BinOp::LogicalImplies => unreachable!()
         }
     }
 }
@@ -0,0 +1,165 @@
+use std::fmt;
+use crate::Span;
+
+// ===================================================================
+// Source Map
+// ===================================================================
+
+/// Maps character offsets within a source file back to lines and
+/// columns.  Offsets here are _character_ offsets (not byte offsets),
+/// as used by the `Lexer` and hence by every `Token` and `Span`.
+pub struct SourceMap {
+    /// Character sequence of the source file.
+    chars: Vec<char>,
+    /// Offset of the first character of each line.
+    lines: Vec<usize>
+}
+
+impl SourceMap {
+    /// Construct a source map from a string slice.
+    pub fn new(content: &str) -> Self {
+        Self::from_chars(content.chars().collect())
+    }
+
+    /// Construct a source map from a character sequence (e.g. the
+    /// buffer held by a `Lexer`).
+    pub fn from_chars(chars: Vec<char>) -> Self {
+        let mut lines = vec![0];
+        //
+        for (i,c) in chars.iter().enumerate() {
+            if *c == '\n' { lines.push(i+1); }
+        }
+        //
+        Self{chars,lines}
+    }
+
+    /// Get the number of lines in this source file.
+    pub fn num_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Determine the line and column of a given character offset.
+    /// Both are numbered from `1`.  An offset beyond the end of the
+    /// file is mapped to the end of the last line.
+    pub fn line_col(&self, offset: usize) -> (usize,usize) {
+        let offset = offset.min(self.chars.len());
+        // Find last line starting at or before offset
+        let line = match self.lines.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1
+        };
+        (line+1, offset - self.lines[line] + 1)
+    }
+
+    /// Get the text of a given line (numbered from `1`), excluding its
+    /// line terminator.
+    pub fn line(&self, line: usize) -> String {
+        let start = self.lines[line-1];
+        let mut end = if line < self.lines.len() {
+            self.lines[line] - 1
+        } else {
+            self.chars.len()
+        };
+        // Strip carriage return (if applicable)
+        if end > start && self.chars[end-1] == '\r' { end -= 1; }
+        //
+        self.chars[start..end].iter().collect()
+    }
+}
+
+// ===================================================================
+// Diagnostic
+// ===================================================================
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Level {
+    Error,
+    Warning
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Error => write!(f,"error"),
+            Level::Warning => write!(f,"warning")
+        }
+    }
+}
+
+/// A message reported against a source file, optionally identifying
+/// the span of characters responsible.  This can be rendered in the
+/// style of `rustc` (i.e. with the offending line and a caret
+/// underline).
+#[derive(Clone,Debug,PartialEq)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Option<Span>,
+    /// Additional information rendered after the source snippet.
+    pub notes: Vec<String>
+}
+
+impl Diagnostic {
+    pub fn new(level: Level, message: &str, span: Option<Span>) -> Self {
+        Self{level,message: message.to_string(),span,notes: Vec::new()}
+    }
+
+    /// Attach a note to this diagnostic.
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn error(message: &str, span: Option<Span>) -> Self {
+        Self::new(Level::Error,message,span)
+    }
+
+    pub fn warning(message: &str, span: Option<Span>) -> Self {
+        Self::new(Level::Warning,message,span)
+    }
+
+    /// Render this diagnostic for a given file.  For example:
+    ///
+    /// ```text
+    /// error: expected `;`, found `}`
+    ///  --> test.tfl:3:14
+    ///   |
+    /// 3 |    assert x > 0
+    ///   |             ^
+    /// ```
+    ///
+    /// Notes are rendered beneath the snippet (e.g. `  = note: ...`).
+    pub fn render(&self, filename: &str, map: &SourceMap) -> String {
+        let mut out = format!("{}: {}\n",self.level,self.message);
+        let mut gutter = String::new();
+        //
+        match self.span {
+            None => {
+                out.push_str(&format!(" --> {filename}\n"));
+            }
+            Some(span) => {
+                let (line,col) = map.line_col(span.start);
+                let text = map.line(line);
+                gutter = " ".repeat(line.to_string().len());
+                out.push_str(&format!("{gutter}--> {filename}:{line}:{col}\n"));
+                out.push_str(&format!("{gutter} |\n"));
+                out.push_str(&format!("{line} | {text}\n"));
+                // Construct underline, preserving tabs so that the
+                // carets line up with the text above.
+                let mut underline : String = text.chars().take(col-1).map(|c| {
+                    if c == '\t' { '\t' } else { ' ' }
+                }).collect();
+                // Underline is clipped at the end of the line
+                let available = text.chars().count().saturating_sub(col-1);
+                let width = span.len().min(available).max(1);
+                underline.push_str(&"^".repeat(width));
+                out.push_str(&format!("{gutter} | {underline}\n"));
+            }
+        }
+        //
+        for note in &self.notes {
+            out.push_str(&format!("{gutter} = note: {note}\n"));
+        }
+        out
+    }
+}
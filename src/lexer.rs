@@ -1,3 +1,6 @@
+use std::fmt;
+use crate::ParseError;
+
 // ===================================================================
 // Token
 // ===================================================================
@@ -9,24 +12,31 @@ pub enum TokenType {
     Assume,
     Bar,
     BarBar,
+    BlockComment,
     Bool,
     BoolLiteral(bool),
     Comma,
     Dot,
     DotDot,
+    Equals,
     EqualsEquals,
+    EqualsEqualsRightAngle,
     Else,
     EOF,
     Ensures,
     Function,
     Identifier,
     If,
+    Int,
     IntLiteral,
     LeftAngle,
     LeftAngleEquals,
+    LeftAngleEqualsEqualsRightAngle,
     LeftBrace,
     LeftCurly,
     LeftSquare,
+    Let,
+    LineComment,
     Minus,
     Percent,
     Plus,
@@ -40,12 +50,81 @@ pub enum TokenType {
     RightSlash,
     RightSquare,
     SemiColon,
+    Shreak,
     ShreakEquals,
     Star,
     Uint,
+    Unknown,
     WhiteSpace
 }
 
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            TokenType::AmpersandAmpersand => "`&&`",
+            TokenType::Assert => "`assert`",
+            TokenType::Assume => "`assume`",
+            TokenType::Bar => "`|`",
+            TokenType::BarBar => "`||`",
+            TokenType::BlockComment => "block comment",
+            TokenType::Bool => "`bool`",
+            TokenType::BoolLiteral(true) => "`true`",
+            TokenType::BoolLiteral(false) => "`false`",
+            TokenType::Comma => "`,`",
+            TokenType::Dot => "`.`",
+            TokenType::DotDot => "`..`",
+            TokenType::Equals => "`=`",
+            TokenType::EqualsEquals => "`==`",
+            TokenType::EqualsEqualsRightAngle => "`==>`",
+            TokenType::Else => "`else`",
+            TokenType::EOF => "end of file",
+            TokenType::Ensures => "`ensures`",
+            TokenType::Function => "`function`",
+            TokenType::Identifier => "identifier",
+            TokenType::If => "`if`",
+            TokenType::Int => "`int`",
+            TokenType::IntLiteral => "integer literal",
+            TokenType::LeftAngle => "`<`",
+            TokenType::LeftAngleEquals => "`<=`",
+            TokenType::LeftAngleEqualsEqualsRightAngle => "`<==>`",
+            TokenType::LeftBrace => "`(`",
+            TokenType::LeftCurly => "`{`",
+            TokenType::LeftSquare => "`[`",
+            TokenType::Let => "`let`",
+            TokenType::LineComment => "line comment",
+            TokenType::Minus => "`-`",
+            TokenType::Percent => "`%`",
+            TokenType::Plus => "`+`",
+            TokenType::PlusPlus => "`++`",
+            TokenType::Requires => "`requires`",
+            TokenType::RightAngle => "`>`",
+            TokenType::RightAngleEquals => "`>=`",
+            TokenType::RightArrow => "`->`",
+            TokenType::RightBrace => "`)`",
+            TokenType::RightCurly => "`}`",
+            TokenType::RightSlash => "`/`",
+            TokenType::RightSquare => "`]`",
+            TokenType::SemiColon => "`;`",
+            TokenType::Shreak => "`!`",
+            TokenType::ShreakEquals => "`!=`",
+            TokenType::Star => "`*`",
+            TokenType::Uint => "`uint`",
+            TokenType::Unknown => "unknown character",
+            TokenType::WhiteSpace => "whitespace"
+        };
+        write!(f,"{s}")
+    }
+}
+
+impl TokenType {
+    /// Determine whether this token is _trivia_ (i.e. whitespace or a
+    /// comment).  Trivia carries no meaning for the parser, and is
+    /// therefore skipped.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenType::WhiteSpace | TokenType::LineComment | TokenType::BlockComment)
+    }
+}
+
 /// Represents a single token generated from a string slice.  This
 /// identifies where the token starts and ends in the original slice.
 #[derive(Clone,Copy,Debug,PartialEq)]
@@ -60,6 +139,35 @@ impl Token {
     pub fn relocate(&mut self, offset: usize) {
         self.offset = offset;
     }
+
+    /// Get the span of characters covered by this token.
+    pub fn span(&self) -> Span {
+        Span::new(self.offset,self.offset + self.len)
+    }
+}
+
+/// Represents a contiguous range of characters in the original
+/// source, starting at `start` (inclusive) and finishing at `end`
+/// (exclusive).
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        assert!(start <= end);
+        Self{start,end}
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
 }
 
 /// Represents the end of the input stream.  This is helpful because
@@ -78,6 +186,8 @@ const ENSURES : &[char] = &['e','n','s','u','r','e','s'];
 const FALSE : &[char] = &['f','a','l','s','e'];
 const FUNCTION : &[char] = &['f','u','n','c','t','i','o','n'];
 const IF : &[char] = &['i','f'];
+const INT : &[char] = &['i','n','t'];
+const LET : &[char] = &['l','e','t'];
 const REQUIRES : &[char] = &['r','e','q','u','i','r','e','s'];
 const TRUE : &[char] = &['t','r','u','e'];
 const UINT : &[char] = &['u','i','n','t'];
@@ -86,16 +196,19 @@ const UINT : &[char] = &['u','i','n','t'];
 // Scanners
 // ===================================================================
 
-/// Scan for next token
+/// Scan for next token.  If no token can be matched, then an
+/// `Unknown` token covering the offending character is returned.
 fn scan(input: &[char]) -> Token {
     if let Ok(tok) = scan_whitespace(input) { return tok; }
+    if let Ok(tok) = scan_comment(input) { return tok; }
+    if let Ok(tok) = scan_long_operators(input) { return tok; }
     if let Ok(tok) = scan_double_operators(input) { return tok; }
     if let Ok(tok) = scan_single_operators(input) { return tok; }
     if let Ok(tok) = scan_int_literal(input) { return tok; }
     if let Ok(tok) = scan_keyword(input) { return tok; }
     if let Ok(tok) = scan_identifier(input) { return tok; }
-    // TODO: better error handling :)
-    panic!("unexpected character: {}", input[0]);
+    // Nothing matched
+    Token{kind: TokenType::Unknown, offset: 0, len: 1}
 }
 
 /// Scan all single-character operators.
@@ -107,11 +220,13 @@ fn scan_single_operators(input: &[char]) -> Result<Token,()> {
             '|' => TokenType::Bar,
             ',' => TokenType::Comma,
             '.' => TokenType::Dot,
+            '=' => TokenType::Equals,
             '<' => TokenType::LeftAngle,
             '(' => TokenType::LeftBrace,
             '{' => TokenType::LeftCurly,
             '[' => TokenType::LeftSquare,
             '-' => TokenType::Minus,
+            '!' => TokenType::Shreak,
             '%' => TokenType::Percent,
             '+' => TokenType::Plus,
             '>' => TokenType::RightAngle,
@@ -128,6 +243,17 @@ fn scan_single_operators(input: &[char]) -> Result<Token,()> {
     }
 }
 
+/// Scan all operators consisting of more than two characters.
+fn scan_long_operators(input: &[char]) -> Result<Token,()> {
+    if input.starts_with(&['<','=','=','>']) {
+        Ok(Token{kind:TokenType::LeftAngleEqualsEqualsRightAngle, offset:0, len:4})
+    } else if input.starts_with(&['=','=','>']) {
+        Ok(Token{kind:TokenType::EqualsEqualsRightAngle, offset:0, len:3})
+    } else {
+        Err(())
+    }
+}
+
 /// Scan all double-character operators.
 fn scan_double_operators(input: &[char]) -> Result<Token,()> {
     if input.len() <= 1 {
@@ -200,6 +326,8 @@ fn scan_keyword(input: &[char]) -> Result<Token,()> {
         ENSURES => TokenType::Ensures,
         FALSE => TokenType::BoolLiteral(false),
         IF => TokenType::If,
+        INT => TokenType::Int,
+        LET => TokenType::Let,
         FUNCTION => TokenType::Function,
         REQUIRES => TokenType::Requires,
         TRUE => TokenType::BoolLiteral(true),
@@ -210,6 +338,42 @@ fn scan_keyword(input: &[char]) -> Result<Token,()> {
     Ok(Token{kind:t,offset:0,len:ident.len})
 }
 
+/// Scan a comment, which is either a line comment (e.g. `// ...`) or a
+/// block comment (e.g. `/* ... */`).  Block comments may be nested.
+/// An unterminated block comment is reported as an `Unknown` token.
+fn scan_comment(input: &[char]) -> Result<Token,()> {
+    if input.len() < 2 || input[0] != '/' {
+        Err(())
+    } else if input[1] == '/' {
+        let mut i = 2;
+        // Continue until end of line (or input)
+        while i < input.len() && input[i] != '\n' {
+            i += 1;
+        }
+        Ok(Token{kind: TokenType::LineComment, offset: 0, len: i})
+    } else if input[1] == '*' {
+        let mut i = 2;
+        let mut depth = 1;
+        // Continue until matching close
+        while depth > 0 {
+            if i + 1 >= input.len() {
+                return Ok(Token{kind: TokenType::Unknown, offset: 0, len: 2});
+            } else if input[i] == '/' && input[i+1] == '*' {
+                depth += 1;
+                i += 2;
+            } else if input[i] == '*' && input[i+1] == '/' {
+                depth -= 1;
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(Token{kind: TokenType::BlockComment, offset: 0, len: i})
+    } else {
+        Err(())
+    }
+}
+
 fn scan_whitespace(input: &[char]) -> Result<Token,()> {
     let mut i = 0;
     // Continue matching
@@ -245,53 +409,90 @@ fn is_whitespace(c: char) -> bool {
 // Lexer
 // ===================================================================
 
+/// Responsible for turning a character sequence into tokens.  The
+/// input is tokenised once, up front, and the parser then moves a
+/// cursor through the resulting token buffer.  Since lexical errors
+/// are only reported when the parser reaches them, tokens preceding
+/// an error remain available.
 pub struct Lexer {
     /// Character sequence being parsed
     chars: Vec<char>,
-    /// Index into char sequence.
-    offset: usize
+    /// Tokens (excluding trivia) of the character sequence.  Unless
+    /// an error was encountered, this is terminated by `EOF`.
+    tokens: Vec<Token>,
+    /// Index into token sequence.
+    index: usize,
+    /// Error encountered during tokenisation (if any)
+    error: Option<ParseError>
 }
 
 impl Lexer {
     /// Construct a parser from a string slice.
     pub fn new(content: &str) -> Self {
         // Convert string slice into Vec<char>
-        let chars = content.chars().collect();
+        let chars : Vec<char> = content.chars().collect();
+        let mut tokens = Vec::new();
+        let mut error = None;
+        // Tokenise input
+        for tok in TokenStream::new(&chars,false) {
+            match tok {
+                Ok(tok) => tokens.push(tok),
+                Err(e) => error = Some(e)
+            }
+        }
+        // Terminate token stream (if applicable)
+        if error.is_none() {
+            tokens.push(Token{kind: TokenType::EOF, offset: chars.len(), len: 0});
+        }
         // Done
-        Self{chars, offset: 0}
+        Self{chars, tokens, index: 0, error}
     }
 
-    pub fn lookahead(&mut self, mut n: usize) -> Token {
-        let mut tok = Self::lookahead_from(&self.chars,self.offset);
+    pub fn lookahead(&self, n: usize) -> Result<Token,ParseError> {
+        let i = self.index + n;
         //
-        while n > 0 {
-            tok = Self::lookahead_from(&self.chars,tok.offset + tok.len);
-            n -= 1;
+        if i < self.tokens.len() {
+            Ok(self.tokens[i])
+        } else if let Some(e) = &self.error {
+            Err(e.clone())
+        } else {
+            // Stick at EOF
+            Ok(self.tokens[self.tokens.len()-1])
         }
-        //
-        tok
     }
 
-    pub fn matches(&mut self, kind: TokenType) -> bool {
-        let lookahead = self.lookahead(0);
+    pub fn matches(&mut self, kind: TokenType) -> Result<bool,ParseError> {
+        let lookahead = self.lookahead(0)?;
         //
         if lookahead.kind == kind {
             self.accept(&lookahead);
-            true
+            Ok(true)
         } else {
-            false
+            Ok(false)
         }
     }
 
-    pub fn match_any(&mut self, tokens: &[TokenType]) -> Option<Token> {
-        let lookahead = self.lookahead(0);
+    pub fn match_any(&mut self, tokens: &[TokenType]) -> Result<Option<Token>,ParseError> {
+        let lookahead = self.lookahead(0)?;
         //
         for t in tokens {
             if  lookahead.kind == *t {
-                return Some(self.expect(*t))
+                self.accept(&lookahead);
+                return Ok(Some(lookahead));
             }
         }
-        None
+        Ok(None)
+    }
+
+    /// Get the offset immediately following the last token accepted
+    /// by this lexer.
+    pub fn offset(&self) -> usize {
+        if self.index == 0 {
+            0
+        } else {
+            let last = self.tokens[self.index-1];
+            last.offset + last.len
+        }
     }
 
     /// Construct a string from a span of the character array
@@ -302,56 +503,102 @@ impl Lexer {
         slice.iter().collect()
     }
 
+    /// Accept a given token, which must be the next token in the
+    /// sequence.
     pub fn accept(&mut self, token: &Token) {
-        // Skip whitespace (this could be made more efficient)
-        self.skip_whitespace();
         // sanity check
-        assert!(self.lookahead(0) == *token);
-        // for now
-        self.offset += token.len
+        debug_assert!(self.tokens.get(self.index) == Some(token));
+        //
+        if token.kind != TokenType::EOF {
+            self.index += 1;
+        }
     }
 
-    pub fn expect(&mut self, kind: TokenType) -> Token {
-        let tok = self.lookahead(0);
+    pub fn expect(&mut self, kind: TokenType) -> Result<Token,ParseError> {
+        let tok = self.lookahead(0)?;
         if tok.kind != kind {
-            panic!("expected {kind:?} found {tok:?}");
+            return Err(ParseError::expected(tok,&[kind]));
         }
         self.accept(&tok);
-        tok
+        Ok(tok)
     }
 
-    // ===============================================================
+    /// Construct a stream of the tokens in this lexer's input.  When
+    /// `trivia` is set, the stream includes whitespace and comments
+    /// (which are otherwise skipped).  This is useful for tools which
+    /// must preserve the original text (e.g. a formatter).
+    pub fn token_stream(&self, trivia: bool) -> TokenStream<'_> {
+        TokenStream::new(&self.chars,trivia)
+    }
+}
 
-    fn skip_whitespace(&mut self) {
-        if self.offset < self.chars.len() {
-            // Scan operators
-            let tok = scan(&self.chars[self.offset..]);
-            // See what we got
-            if tok.kind == TokenType::WhiteSpace {
-                self.offset += tok.len;
+// ===================================================================
+// Token Stream
+// ===================================================================
+
+/// An iterator over the tokens of a character sequence, which
+/// optionally includes trivia (i.e. whitespace and comments).  The
+/// stream finishes at the end of input, or after the first error.
+pub struct TokenStream<'a> {
+    /// Character sequence being scanned
+    chars: &'a [char],
+    /// Index into char sequence.
+    offset: usize,
+    /// Whether or not to include trivia
+    trivia: bool,
+    /// Set once an error has been reported
+    failed: bool
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(chars: &'a [char], trivia: bool) -> Self {
+        Self{chars, offset: 0, trivia, failed: false}
+    }
+}
+
+impl<'a> Iterator for TokenStream<'a> {
+    type Item = Result<Token,ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed && self.offset < self.chars.len() {
+            match scan_at(self.chars,self.offset) {
+                Ok(tok) => {
+                    self.offset += tok.len;
+                    if self.trivia || !tok.kind.is_trivia() {
+                        return Some(Ok(tok));
+                    }
+                }
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
             }
         }
+        None
     }
+}
 
-    fn lookahead_from(chars: &[char], offset: usize) -> Token {
-        if offset >= chars.len() {
-            EOF
-        } else {
-            // Create slice
-            let slice = &chars[offset..];
-            // Scan operators
-            let mut tok = scan(slice);
-            //
-            if tok.kind == TokenType::WhiteSpace {
-                // Continue
-                Self::lookahead_from(chars, offset + tok.len)
+/// Scan the token starting at a given offset within a character
+/// sequence.  This returns an `EOF` token at the end of input, and
+/// reports an error for any character which cannot be scanned.
+fn scan_at(chars: &[char], offset: usize) -> Result<Token,ParseError> {
+    if offset >= chars.len() {
+        Ok(Token{kind: TokenType::EOF, offset: chars.len(), len: 0})
+    } else {
+        // Scan operators
+        let mut tok = scan(&chars[offset..]);
+        // Set its offset
+        tok.relocate(offset);
+        // Check for unknown characters
+        if tok.kind == TokenType::Unknown {
+            let msg = if chars[offset..].starts_with(&['/','*']) {
+                "unterminated block comment".to_string()
             } else {
-                // Set its offset
-                tok.relocate(offset);
-                // Done
-                tok
-            }
+                format!("unexpected character `{}`",chars[offset])
+            };
+            return Err(ParseError::new(tok,Vec::new(),msg));
         }
+        // Done
+        Ok(tok)
     }
-
 }
@@ -1,12 +1,16 @@
 mod ast;
 mod codegen;
+mod diagnostic;
 mod lexer;
 mod parser;
+mod typing;
 mod verifier;
 pub mod circuit;
 
 pub use ast::*;
 pub use codegen::*;
+pub use diagnostic::*;
 pub use lexer::*;
 pub use parser::*;
+pub use typing::*;
 pub use verifier::*;
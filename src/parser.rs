@@ -1,12 +1,31 @@
-use crate::{BinOp,EOF,Lexer,Function,Term,Token,TokenType,SyntacticHeap};
+use std::fmt;
+use std::error::Error;
+use crate::{BinOp,Lexer,Function,Span,Term,Token,TokenType,SyntacticHeap,UnaryOp};
 
 /// Defines the set of tokens which are considered to identify logical
-/// connectives (e.g. `&&`, `||`, etc).
-pub const LOGICAL_CONNECTIVES : &[TokenType] = &[
-    TokenType::AmpersandAmpersand,
+/// equivalence (i.e. `<==>`).
+pub const LOGICAL_EQUIVALENCES : &[TokenType] = &[
+    TokenType::LeftAngleEqualsEqualsRightAngle
+];
+
+/// Defines the set of tokens which are considered to identify logical
+/// implication (i.e. `==>`).
+pub const LOGICAL_IMPLICATIONS : &[TokenType] = &[
+    TokenType::EqualsEqualsRightAngle
+];
+
+/// Defines the set of tokens which are considered to identify logical
+/// disjunction (i.e. `||`).
+pub const LOGICAL_DISJUNCTIONS : &[TokenType] = &[
     TokenType::BarBar
 ];
 
+/// Defines the set of tokens which are considered to identify logical
+/// conjunction (i.e. `&&`).
+pub const LOGICAL_CONJUNCTIONS : &[TokenType] = &[
+    TokenType::AmpersandAmpersand
+];
+
 /// Defines the set of tokens which are considered to identify
 /// arithmetic comparators (e.g. `<`, `<=`, `==`, etc).
 pub const ARITHMETIC_COMPARATORS : &[TokenType] = &[
@@ -19,21 +38,99 @@ pub const ARITHMETIC_COMPARATORS : &[TokenType] = &[
 ];
 
 /// Defines the set of tokens which are considered to identify
-/// arithmetic operators (e.g. `+`, `-`, `*`, etc).
-pub const ARITHMETIC_OPERATORS : &[TokenType] = &[
+/// additive arithmetic operators (i.e. `+` and `-`).
+pub const ADDITIVE_OPERATORS : &[TokenType] = &[
     TokenType::Minus,
+    TokenType::Plus
+];
+
+/// Defines the set of tokens which are considered to identify
+/// multiplicative arithmetic operators (i.e. `*`, `/` and `%`).
+pub const MULTIPLICATIVE_OPERATORS : &[TokenType] = &[
     TokenType::Percent,
-    TokenType::Plus,
     TokenType::RightSlash,
     TokenType::Star
 ];
 
+/// Defines the precedence hierarchy of binary connectives, starting
+/// with those which bind most tightly.  Connectives at the same level
+/// are left associative, unless they are listed in
+/// `RIGHT_ASSOCIATIVE_CONNECTIVES`.
 pub const BINARY_CONNECTIVES : &[ &[TokenType] ] = &[
-    ARITHMETIC_OPERATORS,
+    MULTIPLICATIVE_OPERATORS,
+    ADDITIVE_OPERATORS,
     ARITHMETIC_COMPARATORS,
-    LOGICAL_CONNECTIVES
+    LOGICAL_CONJUNCTIONS,
+    LOGICAL_DISJUNCTIONS,
+    LOGICAL_IMPLICATIONS,
+    LOGICAL_EQUIVALENCES
+];
+
+/// Defines the set of binary connectives which are right associative
+/// (e.g. `a ==> b ==> c` is parsed as `a ==> (b ==> c)`).
+pub const RIGHT_ASSOCIATIVE_CONNECTIVES : &[TokenType] = LOGICAL_IMPLICATIONS;
+
+/// Defines the set of tokens which are considered to identify unary
+/// operators (e.g. `!` and `-`).
+pub const UNARY_OPERATORS : &[TokenType] = &[
+    TokenType::Minus,
+    TokenType::Shreak
 ];
 
+// ===================================================================
+// Parse Error
+// ===================================================================
+
+/// Identifies a specific error arising during parsing.  This records
+/// the offending token (hence, where in the source the error arose),
+/// along with the set of tokens which would have been acceptable at
+/// that point (if known).
+#[derive(Clone,Debug,PartialEq)]
+pub struct ParseError {
+    /// Token at which the error was detected.
+    pub token: Token,
+    /// Set of tokens which were expected at this point (may be
+    /// empty).
+    pub expected: Vec<TokenType>,
+    /// Human-readable description of the error.
+    pub message: String
+}
+
+impl ParseError {
+    pub fn new(token: Token, expected: Vec<TokenType>, message: String) -> Self {
+        Self{token,expected,message}
+    }
+
+    /// Construct an error for the case where one of a given set of
+    /// tokens was expected, but `token` was found instead.
+    pub fn expected(token: Token, expected: &[TokenType]) -> Self {
+        let mut message = "expected ".to_string();
+        for (i,t) in expected.iter().enumerate() {
+            if i != 0 {
+                message.push_str(if i+1 == expected.len() { " or " } else { ", " });
+            }
+            message.push_str(&t.to_string());
+        }
+        message.push_str(&format!(", found {}",token.kind));
+        Self::new(token,expected.to_vec(),message)
+    }
+
+    /// Get the span of characters at which this error arose.
+    pub fn span(&self) -> Span {
+        self.token.span()
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ParseError {
+
+}
+
 // ===================================================================
 // Parser
 // ===================================================================
@@ -58,23 +155,23 @@ impl Parser {
     }
 
     /// Parse a line of text into a term.
-    pub fn parse(&mut self) -> Result<Vec<usize>,()> {
+    pub fn parse(&mut self) -> Result<Vec<usize>,ParseError> {
         let mut terms = Vec::new();
         //
-        while self.lexer.lookahead(0) != EOF {
+        while self.lexer.lookahead(0)?.kind != TokenType::EOF {
             terms.push(self.parse_declaration()?);
         }
         //
         Ok(terms)
     }
 
-    fn parse_declaration(&mut self) -> Result<usize,()> {
-        let lookahead = self.lexer.lookahead(0);
+    fn parse_declaration(&mut self) -> Result<usize,ParseError> {
+        let lookahead = self.lexer.lookahead(0)?;
         //
         match lookahead.kind {
             TokenType::Function => self.parse_decl_function(),
             _ => {
-                Err(())
+                Err(ParseError::expected(lookahead,&[TokenType::Function]))
             }
         }
     }
@@ -83,14 +180,15 @@ impl Parser {
     // Declarations
     // ===============================================================
 
-    fn parse_decl_function(&mut self) -> Result<usize,()> {
-        self.lexer.expect(TokenType::Function);
+    fn parse_decl_function(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Function)?;
         // Parse function name
-        let id = self.lexer.expect(TokenType::Identifier);
+        let id = self.lexer.expect(TokenType::Identifier)?;
         // Parse declared parameters
         let params = self.parse_decl_params()?;
         // Parse optional return
-        let rets = if self.lexer.matches(TokenType::RightArrow) {
+        let rets = if self.lexer.matches(TokenType::RightArrow)? {
 	    self.parse_decl_params()?
         } else {
             Vec::new()
@@ -102,38 +200,38 @@ impl Parser {
         // Done
         let name = self.lexer.to_string(&id);
         let fun = Function{name,params,rets,requires,ensures,body};
-        Ok(self.heap.alloc(Term::Function(fun)))
+        Ok(self.alloc(Term::Function(fun),start))
     }
 
-    fn parse_decl_params(&mut self) -> Result<Vec<(usize,String)>,()> {
+    fn parse_decl_params(&mut self) -> Result<Vec<(usize,String)>,ParseError> {
         let mut params = Vec::new();
-        self.lexer.expect(TokenType::LeftBrace);
-        let mut lookahead = self.lexer.lookahead(0);
+        self.lexer.expect(TokenType::LeftBrace)?;
+        let mut lookahead = self.lexer.lookahead(0)?;
         //
         while lookahead.kind != TokenType::RightBrace {
-            if !params.is_empty() { self.lexer.expect(TokenType::Comma); }
+            if !params.is_empty() { self.lexer.expect(TokenType::Comma)?; }
             let t = self.parse_type()?;
             let var = self.parse_identifier()?;
             params.push((t,var));
-            lookahead = self.lexer.lookahead(0);
+            lookahead = self.lexer.lookahead(0)?;
         }
         // Done
-        self.lexer.expect(TokenType::RightBrace);
+        self.lexer.expect(TokenType::RightBrace)?;
         Ok(params)
     }
 
-    fn parse_decl_requires(&mut self) -> Result<Vec<usize>,()> {
+    fn parse_decl_requires(&mut self) -> Result<Vec<usize>,ParseError> {
         let mut requires = Vec::new();
-        while self.lexer.matches(TokenType::Requires) {
+        while self.lexer.matches(TokenType::Requires)? {
             requires.push(self.parse_expr()?);
         }
         Ok(requires)
     }
 
-    fn parse_decl_ensures(&mut self) -> Result<Vec<usize>,()> {
+    fn parse_decl_ensures(&mut self) -> Result<Vec<usize>,ParseError> {
         // TODO: include return values
         let mut ensures = Vec::new();
-        while self.lexer.matches(TokenType::Ensures) {
+        while self.lexer.matches(TokenType::Ensures)? {
             ensures.push(self.parse_expr()?);
         }
         Ok(ensures)
@@ -145,22 +243,26 @@ impl Parser {
 
     /// Parse a _block_, which is a term wrapped in curly braces
     /// (e.g. `{ [] }`).
-    fn parse_block(&mut self) -> Result<usize,()> {
+    fn parse_block(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
         let mut terms = Vec::new();
         // Blocks begin with open curly brace
-        self.lexer.expect(TokenType::LeftCurly);
+        self.lexer.expect(TokenType::LeftCurly)?;
         // Keep going until closing curly brace
-        while self.lexer.lookahead(0).kind != TokenType::RightCurly {
+        while self.lexer.lookahead(0)?.kind != TokenType::RightCurly {
             // Parse separator (if applicable)
-            if !terms.is_empty() { self.lexer.expect(TokenType::SemiColon); }
+            if !terms.is_empty() { self.lexer.expect(TokenType::SemiColon)?; }
             // Parse statement or terminating expression
-            match self.lexer.lookahead(0).kind {
+            match self.lexer.lookahead(0)?.kind {
                 TokenType::Assert => {
                     terms.push(self.parse_stmt_assert()?);
                 }
                 TokenType::Assume => {
                     terms.push(self.parse_stmt_assume()?);
                 }
+                TokenType::Let => {
+                    terms.push(self.parse_stmt_let()?);
+                }
                 TokenType::RightCurly => {
                     // No trailing expression
                 }
@@ -171,61 +273,109 @@ impl Parser {
             }
         }
         //
-        self.lexer.expect(TokenType::RightCurly);
-        Ok(self.heap.alloc(Term::Block(terms)))
+        self.lexer.expect(TokenType::RightCurly)?;
+        Ok(self.alloc(Term::Block(terms),start))
     }
 
-    fn parse_stmt_assert(&mut self) -> Result<usize,()> {
-        self.lexer.expect(TokenType::Assert);
+    fn parse_stmt_assert(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Assert)?;
         let expr = self.parse_expr()?;
-        Ok(self.heap.alloc(Term::Assert(expr)))
+        Ok(self.alloc(Term::Assert(expr),start))
     }
 
-    fn parse_stmt_assume(&mut self) -> Result<usize,()> {
-        self.lexer.expect(TokenType::Assume);
+    fn parse_stmt_assume(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Assume)?;
+        let expr = self.parse_expr()?;
+        Ok(self.alloc(Term::Assume(expr),start))
+    }
+
+    /// Parse a `let` statement, which either binds a single variable
+    /// (e.g. `let x = e`) or destructures a tuple (e.g. `let (x,y) =
+    /// e`).
+    fn parse_stmt_let(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Let)?;
+        if self.lexer.matches(TokenType::LeftBrace)? {
+            let mut names = vec![self.parse_identifier()?];
+            while self.lexer.matches(TokenType::Comma)? {
+                names.push(self.parse_identifier()?);
+            }
+            self.lexer.expect(TokenType::RightBrace)?;
+            self.lexer.expect(TokenType::Equals)?;
+            let expr = self.parse_expr()?;
+            return Ok(self.alloc(Term::LetTuple(names,expr),start));
+        }
+        let var = self.lexer.expect(TokenType::Identifier)?;
+        self.lexer.expect(TokenType::Equals)?;
         let expr = self.parse_expr()?;
-        Ok(self.heap.alloc(Term::Assume(expr)))
+        let name = self.lexer.to_string(&var);
+        Ok(self.alloc(Term::Let(name,expr),start))
     }
 
     // ===============================================================
     // Expressions
     // ===============================================================
 
-    pub fn parse_expr(&mut self) -> Result<usize,()> {
-        self.parse_expr_binary(3)
+    pub fn parse_expr(&mut self) -> Result<usize,ParseError> {
+        self.parse_expr_binary(BINARY_CONNECTIVES.len())
     }
 
     /// Parse a binary expression at a given _level_.  Higher levels
     /// indicate expressions which bind _less tightly_.  Furthermore,
     /// level `0` corresponds simply to parsing a unary expression.
-    fn parse_expr_binary(&mut self, level: usize) -> Result<usize,()> {
+    /// Most connectives associate to the left, hence `1 - 2 - 3` is
+    /// parsed as `(1 - 2) - 3`.
+    fn parse_expr_binary(&mut self, level: usize) -> Result<usize,ParseError> {
         if level == 0 {
-            self.parse_expr_postfix()
+            self.parse_expr_unary()
         } else {
             let tokens = BINARY_CONNECTIVES[level-1];
             // Parse level below
-    	    let lhs = self.parse_expr_binary(level-1)?;
-            // Check whether binary connective follows
-            match self.lexer.match_any(tokens) {
-                Some(t) => {
-                    // FIXME: turn this into a loop!
-	            let rhs = self.parse_expr_binary(level-1)?;
-                    // NOTE: following is safe because can only match
-                    // tokens which will be accepted.
-                    let bop = Self::binop_from_token(t.kind).unwrap();
-                    // Done
-                    Ok(self.heap.alloc(Term::Binary(bop,lhs,rhs)))
-                }
-                None => Ok(lhs)
+    	    let mut lhs = self.parse_expr_binary(level-1)?;
+            // Continue whilst binary connectives follow
+            while let Some(t) = self.lexer.match_any(tokens)? {
+                // Right associative connectives consume the remainder
+                // of the chain at this level.
+                let rhs = if RIGHT_ASSOCIATIVE_CONNECTIVES.contains(&t.kind) {
+                    self.parse_expr_binary(level)?
+                } else {
+                    self.parse_expr_binary(level-1)?
+                };
+                // NOTE: following is safe because can only match
+                // tokens which will be accepted.
+                let bop = Self::binop_from_token(t.kind).unwrap();
+                let start = self.heap.span(lhs).start;
+                lhs = self.alloc(Term::Binary(bop,lhs,rhs),start);
+            }
+            // Done
+            Ok(lhs)
+        }
+    }
+
+    /// Parse a unary expression, which is a postfix expression
+    /// preceded by zero or more unary operators (e.g. `!b` or `-x`).
+    fn parse_expr_unary(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        //
+        match self.lexer.match_any(UNARY_OPERATORS)? {
+            Some(t) => {
+                let src = self.parse_expr_unary()?;
+                // NOTE: following is safe because can only match
+                // tokens which will be accepted.
+                let uop = Self::unop_from_token(t.kind).unwrap();
+                Ok(self.alloc(Term::Unary(uop,src),start))
             }
+            None => self.parse_expr_postfix()
         }
     }
 
-    fn parse_expr_postfix(&mut self) -> Result<usize,()> {
+    fn parse_expr_postfix(&mut self) -> Result<usize,ParseError> {
         // Parse the source term
         let mut src = self.parse_expr_unit()?;
         //
-        let mut lookahead = self.lexer.lookahead(0);
+        let mut lookahead = self.lexer.lookahead(0)?;
         // Attempt to parse a postfix operator
         while Self::is_postfix_operator(lookahead) {
             match lookahead.kind {
@@ -238,14 +388,14 @@ impl Parser {
                 _ => {}
             }
             // Continue
-            lookahead = self.lexer.lookahead(0);
+            lookahead = self.lexer.lookahead(0)?;
         }
         // Done
         Ok(src)
     }
 
-    fn parse_expr_unit(&mut self) -> Result<usize,()> {
-        let lookahead = self.lexer.lookahead(0);
+    fn parse_expr_unit(&mut self) -> Result<usize,ParseError> {
+        let lookahead = self.lexer.lookahead(0)?;
         //
         match lookahead.kind {
             TokenType::Bar => self.parse_expr_arraylength(),
@@ -254,7 +404,7 @@ impl Parser {
             TokenType::LeftSquare => self.parse_expr_arrayconstructor(),
             TokenType::Identifier => {
                 // Disambiguate static invocation from variable access
-                if self.lexer.lookahead(1).kind == TokenType::LeftBrace {
+                if self.lexer.lookahead(1)?.kind == TokenType::LeftBrace {
                     self.parse_expr_staticinvoke()
                 } else {
                     self.parse_expr_varaccess()
@@ -263,138 +413,146 @@ impl Parser {
             TokenType::IntLiteral => self.parse_literal_int(),
             TokenType::If => self.parse_expr_ifelse(),
             _ => {
-                panic!("unexpected token {lookahead:?}");
+                let msg = format!("expected expression, found {}",lookahead.kind);
+                Err(ParseError::new(lookahead,Vec::new(),msg))
             }
         }
     }
 
-    fn parse_expr_arrayaccess(&mut self, mut src: usize) -> Result<usize,()> {
-        self.lexer.expect(TokenType::LeftSquare);
+    fn parse_expr_arrayaccess(&mut self, mut src: usize) -> Result<usize,ParseError> {
+        let start = self.heap.span(src).start;
+        self.lexer.expect(TokenType::LeftSquare)?;
         let index = self.parse_expr_unit()?;
         // Check whether access or slice
-        if self.lexer.lookahead(0).kind == TokenType::DotDot {
-            self.lexer.expect(TokenType::DotDot);
+        if self.lexer.lookahead(0)?.kind == TokenType::DotDot {
+            self.lexer.expect(TokenType::DotDot)?;
             let end = self.parse_expr_unit()?;
-            self.lexer.expect(TokenType::RightSquare);
+            self.lexer.expect(TokenType::RightSquare)?;
             // Allocate access expression
-            src = self.heap.alloc(Term::ArraySlice{src,start:index,end});
+            src = self.alloc(Term::ArraySlice{src,start:index,end},start);
         } else {
-            self.lexer.expect(TokenType::RightSquare);
+            self.lexer.expect(TokenType::RightSquare)?;
             // Allocate access expression
-            src = self.heap.alloc(Term::ArrayAccess{src,index});
+            src = self.alloc(Term::ArrayAccess{src,index},start);
         }
         Ok(src)
     }
 
-    fn parse_expr_arrayconstructor(&mut self) -> Result<usize,()> {
+    fn parse_expr_arrayconstructor(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
         let mut terms = Vec::new();
         // Parse left square brace
-        self.lexer.expect(TokenType::LeftSquare);
+        self.lexer.expect(TokenType::LeftSquare)?;
         //
-        if self.lexer.lookahead(0).kind != TokenType::RightSquare {
+        if self.lexer.lookahead(0)?.kind != TokenType::RightSquare {
             let e1 = self.parse_expr()?;
             // Decide between literal and generator
-            if self.lexer.lookahead(0).kind == TokenType::SemiColon {
-                self.lexer.expect(TokenType::SemiColon);
+            if self.lexer.lookahead(0)?.kind == TokenType::SemiColon {
+                self.lexer.expect(TokenType::SemiColon)?;
                 let e2 = self.parse_expr()?;
-                self.lexer.expect(TokenType::RightSquare);
+                self.lexer.expect(TokenType::RightSquare)?;
                 //
-                return Ok(self.heap.alloc(Term::ArrayGenerator(e1,e2)));
+                return Ok(self.alloc(Term::ArrayGenerator(e1,e2),start));
             } else {
                 terms.push(e1);
                 // Parse remainder
-                while self.lexer.lookahead(0).kind != TokenType::RightSquare {
-                    self.lexer.expect(TokenType::Comma);
+                while self.lexer.lookahead(0)?.kind != TokenType::RightSquare {
+                    self.lexer.expect(TokenType::Comma)?;
                     terms.push(self.parse_expr()?);
                 }
             }
         }
         // Match right square brace
-        self.lexer.expect(TokenType::RightSquare);
+        self.lexer.expect(TokenType::RightSquare)?;
         //
-        Ok(self.heap.alloc(Term::ArrayConstructor(terms)))
+        Ok(self.alloc(Term::ArrayConstructor(terms),start))
     }
 
-    fn parse_expr_arraylength(&mut self) -> Result<usize,()> {
-        self.lexer.expect(TokenType::Bar);
+    fn parse_expr_arraylength(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Bar)?;
         // Parse source expression
         let src = self.parse_expr()?;
         //
-        self.lexer.expect(TokenType::Bar);
+        self.lexer.expect(TokenType::Bar)?;
         // Done
-        Ok(self.heap.alloc(Term::ArrayLength(src)))
+        Ok(self.alloc(Term::ArrayLength(src),start))
     }
 
-    fn parse_expr_braced(&mut self) -> Result<usize,()> {
+    fn parse_expr_braced(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
         // Parse opening bracket
-        self.lexer.expect(TokenType::LeftBrace);
+        self.lexer.expect(TokenType::LeftBrace)?;
         // Parse comma-separated terms
         let terms = self.parse_exprs_until(TokenType::RightBrace)?;
         // Parse right brace
-        self.lexer.expect(TokenType::RightBrace);
+        self.lexer.expect(TokenType::RightBrace)?;
         //
         if terms.len() == 1 {
             // Normal braced expression?
-            Ok(self.heap.alloc(Term::Braced(terms[0])))
+            Ok(self.alloc(Term::Braced(terms[0]),start))
         } else {
-            Ok(self.heap.alloc(Term::TupleConstructor(terms)))
+            Ok(self.alloc(Term::TupleConstructor(terms),start))
         }
     }
 
-    fn parse_expr_ifelse(&mut self) -> Result<usize,()> {
-        self.lexer.expect(TokenType::If);
+    fn parse_expr_ifelse(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::If)?;
         // Parse condition
         let cond = self.parse_expr()?;
         // Parse true branch
         let tt = self.parse_block()?;
         // Parse false branch (currently required)
-        self.lexer.expect(TokenType::Else);
+        self.lexer.expect(TokenType::Else)?;
         let ff = self.parse_block()?;
         // Done
-        Ok(self.heap.alloc(Term::IfElse{cond,tt,ff}))
+        Ok(self.alloc(Term::IfElse{cond,tt,ff},start))
     }
 
-    fn parse_expr_staticinvoke(&mut self) -> Result<usize,()> {
-        let id = self.lexer.expect(TokenType::Identifier);
+    fn parse_expr_staticinvoke(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        let id = self.lexer.expect(TokenType::Identifier)?;
         let name = self.lexer.to_string(&id);
         // Parse left brace
-        self.lexer.expect(TokenType::LeftBrace);
+        self.lexer.expect(TokenType::LeftBrace)?;
         // Parse terms within literal
         let terms = self.parse_exprs_until(TokenType::RightBrace)?;
         // Match right brace
-        self.lexer.expect(TokenType::RightBrace);
+        self.lexer.expect(TokenType::RightBrace)?;
         //
-        Ok(self.heap.alloc(Term::StaticInvoke(name,terms)))
+        Ok(self.alloc(Term::StaticInvoke(name,terms),start))
     }
 
-    fn parse_expr_tupleaccess(&mut self, src: usize) -> Result<usize,()> {
-        self.lexer.expect(TokenType::Dot);
-        let tok = self.lexer.expect(TokenType::IntLiteral);
-        let s = self.lexer.to_string(&tok);
-        let i = s.parse::<usize>().unwrap();
-        Ok(self.heap.alloc(Term::TupleAccess(src,i)))
+    fn parse_expr_tupleaccess(&mut self, src: usize) -> Result<usize,ParseError> {
+        let start = self.heap.span(src).start;
+        self.lexer.expect(TokenType::Dot)?;
+        let tok = self.lexer.expect(TokenType::IntLiteral)?;
+        let i = self.parse_usize(tok)?;
+        Ok(self.alloc(Term::TupleAccess(src,i),start))
     }
 
-    fn parse_expr_varaccess(&mut self) -> Result<usize,()> {
-        let id = self.lexer.expect(TokenType::Identifier);
+    fn parse_expr_varaccess(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        let id = self.lexer.expect(TokenType::Identifier)?;
         let name = self.lexer.to_string(&id);
         // Parse as variable access
-        Ok(self.heap.alloc(Term::VarAccess(name)))
+        Ok(self.alloc(Term::VarAccess(name),start))
     }
 
     /// Parse a sequence of zero or more comma-separated terms until a
     /// given end token is encountered.
-    fn parse_exprs_until(&mut self, end: TokenType) -> Result<Vec<usize>,()> {
+    fn parse_exprs_until(&mut self, end: TokenType) -> Result<Vec<usize>,ParseError> {
         let mut terms = Vec::new();
         //
-        let mut lookahead = self.lexer.lookahead(0);
+        let mut lookahead = self.lexer.lookahead(0)?;
         while lookahead.kind != end {
             if !terms.is_empty() {
-                self.lexer.expect(TokenType::Comma);
+                self.lexer.expect(TokenType::Comma)?;
             }
             let ith = self.parse_expr()?;
             terms.push(ith);
-            lookahead = self.lexer.lookahead(0);
+            lookahead = self.lexer.lookahead(0)?;
         }
         //
         Ok(terms)
@@ -404,86 +562,131 @@ impl Parser {
     // Literals
     // ===============================================================
 
-    fn parse_literal_bool(&mut self, val: bool) -> Result<usize,()> {
-        self.lexer.expect(TokenType::BoolLiteral(val));
-        Ok(self.heap.alloc(Term::BoolLiteral(val)))
+    fn parse_literal_bool(&mut self, val: bool) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::BoolLiteral(val))?;
+        Ok(self.alloc(Term::BoolLiteral(val),start))
     }
 
-    fn parse_literal_int(&mut self) -> Result<usize,()> {
-        let tok = self.lexer.expect(TokenType::IntLiteral);
-        let s = self.lexer.to_string(&tok);
-        let i = s.parse::<usize>().unwrap();
-        Ok(self.heap.alloc(Term::IntLiteral(i)))
+    fn parse_literal_int(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        let tok = self.lexer.expect(TokenType::IntLiteral)?;
+        let i = self.parse_usize(tok)?;
+        Ok(self.alloc(Term::IntLiteral(i),start))
     }
 
     // ===============================================================
     // Types
     // ===============================================================
 
-    fn parse_type(&mut self) -> Result<usize,()> {
+    fn parse_type(&mut self) -> Result<usize,ParseError> {
         let mut src = self.parse_unit_type()?;
-        let mut lookahead = self.lexer.lookahead(0);
+        let start = self.heap.span(src).start;
+        let mut lookahead = self.lexer.lookahead(0)?;
         // Parse array type
         while lookahead.kind == TokenType::LeftSquare {
-            self.lexer.expect(TokenType::LeftSquare);
-            self.lexer.expect(TokenType::RightSquare);
+            self.lexer.expect(TokenType::LeftSquare)?;
+            self.lexer.expect(TokenType::RightSquare)?;
             // Allocate access expression
-            src = self.heap.alloc(Term::ArrayType(src));
-            lookahead = self.lexer.lookahead(0);
+            src = self.alloc(Term::ArrayType(src),start);
+            lookahead = self.lexer.lookahead(0)?;
         }
         //
         Ok(src)
     }
 
-    fn parse_unit_type(&mut self) -> Result<usize,()> {
-        let lookahead = self.lexer.lookahead(0);
+    fn parse_unit_type(&mut self) -> Result<usize,ParseError> {
+        let lookahead = self.lexer.lookahead(0)?;
         //
         match lookahead.kind {
+            TokenType::Int => self.parse_int_type(),
             TokenType::Uint => self.parse_uint_type(),
             TokenType::Bool => self.parse_bool_type(),
             TokenType::LeftBrace => self.parse_tuple_type(),
             _ => {
-                panic!("unexpected token {lookahead:?}");
+                let msg = format!("expected type, found {}",lookahead.kind);
+                Err(ParseError::new(lookahead,Vec::new(),msg))
             }
         }
     }
 
-    fn parse_bool_type(&mut self) -> Result<usize,()> {
-        self.lexer.expect(TokenType::Bool);
-        Ok(self.heap.alloc(Term::BoolType))
+    fn parse_bool_type(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Bool)?;
+        Ok(self.alloc(Term::BoolType,start))
+    }
+
+    fn parse_int_type(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Int)?;
+        Ok(self.alloc(Term::IntType(true),start))
     }
 
-    fn parse_uint_type(&mut self) -> Result<usize,()> {
-        self.lexer.expect(TokenType::Uint);
-        Ok(self.heap.alloc(Term::IntType(false)))
+    fn parse_uint_type(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
+        self.lexer.expect(TokenType::Uint)?;
+        Ok(self.alloc(Term::IntType(false),start))
     }
 
-    fn parse_tuple_type(&mut self) -> Result<usize,()> {
+    fn parse_tuple_type(&mut self) -> Result<usize,ParseError> {
+        let start = self.lexer.lookahead(0)?.offset;
         let mut types = Vec::new();
-        self.lexer.expect(TokenType::LeftBrace);
-        while self.lexer.lookahead(0).kind != TokenType::RightBrace {
+        self.lexer.expect(TokenType::LeftBrace)?;
+        while self.lexer.lookahead(0)?.kind != TokenType::RightBrace {
             if !types.is_empty() {
-                self.lexer.expect(TokenType::Comma);
+                self.lexer.expect(TokenType::Comma)?;
             }
             types.push(self.parse_type()?);
         }
-        self.lexer.expect(TokenType::RightBrace);
-        Ok(self.heap.alloc(Term::TupleType(types)))
+        self.lexer.expect(TokenType::RightBrace)?;
+        Ok(self.alloc(Term::TupleType(types),start))
     }
 
     // ===============================================================
     // Misc
     // ===============================================================
 
-    fn parse_identifier(&mut self) -> Result<String,()> {
-        let ith = self.lexer.expect(TokenType::Identifier);
+    /// Allocate a term into the heap whose span begins at a given
+    /// `start` offset, and finishes at the end of the last token
+    /// consumed.
+    fn alloc(&mut self, term: Term, start: usize) -> usize {
+        let span = Span::new(start,self.lexer.offset());
+        self.heap.alloc(term,span)
+    }
+
+    fn parse_identifier(&mut self) -> Result<String,ParseError> {
+        let ith = self.lexer.expect(TokenType::Identifier)?;
         Ok(self.lexer.to_string(&ith))
     }
 
+    /// Parse the contents of an integer literal token into a `usize`.
+    /// This can fail if the literal is too large.
+    fn parse_usize(&self, tok: Token) -> Result<usize,ParseError> {
+        let s = self.lexer.to_string(&tok);
+        match s.parse::<usize>() {
+            Ok(i) => Ok(i),
+            Err(_) => {
+                let msg = format!("integer literal `{s}` is too large");
+                Err(ParseError::new(tok,Vec::new(),msg))
+            }
+        }
+    }
+
     fn is_postfix_operator(token: Token) -> bool {
         token.kind == TokenType::LeftSquare || token.kind == TokenType::Dot
     }
 
+    /// Construct a `UnaryOp` from a `TokenType`.
+    fn unop_from_token(token: TokenType) -> Option<UnaryOp> {
+        let uop = match token {
+            TokenType::Minus => UnaryOp::Negate,
+            TokenType::Shreak => UnaryOp::LogicalNot,
+            // No match
+            _ => { unreachable!(); }
+        };
+        Some(uop)
+    }
+
     /// Construct a `BinOp` from a `TokenType`.
     fn binop_from_token(token: TokenType) -> Option<BinOp> {
 	let bop = match token {
@@ -504,6 +707,8 @@ impl Parser {
             // Logical
             TokenType::AmpersandAmpersand => BinOp::LogicalAnd,
             TokenType::BarBar => BinOp::LogicalOr,
+            TokenType::EqualsEqualsRightAngle => BinOp::LogicalImplies,
+            TokenType::LeftAngleEqualsEqualsRightAngle => BinOp::LogicalIff,
             // No match
 	    _ => { unreachable!(); }
 	};
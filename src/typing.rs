@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::error::Error;
+use crate::{BinOp,Function,SyntacticHeap,Term,UnaryOp};
+
+// ===================================================================
+// Type
+// ===================================================================
+
+/// Represents the type of an expression, as determined by the type
+/// checker.  This is separate from the type terms stored in the heap,
+/// since many expressions have types which are not written down
+/// anywhere in the source.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Type {
+    Array(Box<Type>),
+    Bool,
+    /// Represents the type containing no values, which is used as the
+    /// element type of an empty array (i.e. `[]`).
+    Bottom,
+    Int(bool),
+    Tuple(Vec<Type>),
+    /// Represents the type of statements, which produce no value.
+    Void
+}
+
+impl Type {
+    /// Construct a type from the type term at a given index within
+    /// the heap.
+    pub fn from_heap(heap: &SyntacticHeap, index: usize) -> Type {
+        match heap.get(index) {
+            Term::ArrayType(e) => Type::Array(Box::new(Type::from_heap(heap,*e))),
+            Term::BoolType => Type::Bool,
+            Term::IntType(s) => Type::Int(*s),
+            Term::TupleType(ts) => {
+                Type::Tuple(ts.iter().map(|t| Type::from_heap(heap,*t)).collect())
+            }
+            t => { unreachable!("not a type {t:?}") }
+        }
+    }
+
+    /// Determine whether this is a signed integer type.
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Type::Int(true))
+    }
+
+    /// Determine whether this is an unsigned integer type.
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, Type::Int(false))
+    }
+
+    /// Determine whether this type involves `Bottom` (e.g. as the
+    /// element type of the empty array).
+    pub fn has_bottom(&self) -> bool {
+        match self {
+            Type::Bottom => true,
+            Type::Array(e) => e.has_bottom(),
+            Type::Tuple(ts) => ts.iter().any(|t| t.has_bottom()),
+            _ => false
+        }
+    }
+
+    /// Determine whether every value of this type is also a value of
+    /// another type.  For example, every `uint` is an `int`.
+    pub fn is_subtype(&self, other: &Type) -> bool {
+        self.join(other).as_ref() == Some(other)
+    }
+
+    /// Determine the smallest type which includes all values of this
+    /// type and another.  For example, the join of `uint` and `int`
+    /// is `int`, whilst there is no join of `int` and `bool`.
+    pub fn join(&self, other: &Type) -> Option<Type> {
+        match (self,other) {
+            (Type::Bottom,t)|(t,Type::Bottom) => Some(t.clone()),
+            (Type::Int(s1),Type::Int(s2)) => Some(Type::Int(*s1 || *s2)),
+            (Type::Array(e1),Type::Array(e2)) => {
+                Some(Type::Array(Box::new(e1.join(e2)?)))
+            }
+            (Type::Tuple(ts1),Type::Tuple(ts2)) if ts1.len() == ts2.len() => {
+                let mut ts = Vec::new();
+                for (t1,t2) in ts1.iter().zip(ts2) {
+                    ts.push(t1.join(t2)?);
+                }
+                Some(Type::Tuple(ts))
+            }
+            (t1,t2) if t1 == t2 => Some(t1.clone()),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Array(e) => write!(f,"{e}[]"),
+            Type::Bool => write!(f,"bool"),
+            Type::Bottom => write!(f,"void"),
+            Type::Int(true) => write!(f,"int"),
+            Type::Int(false) => write!(f,"uint"),
+            Type::Tuple(ts) => {
+                write!(f,"(")?;
+                for (i,t) in ts.iter().enumerate() {
+                    if i != 0 { write!(f,",")?; }
+                    write!(f,"{t}")?;
+                }
+                write!(f,")")
+            }
+            Type::Void => write!(f,"void")
+        }
+    }
+}
+
+// ===================================================================
+// Type Error
+// ===================================================================
+
+/// Identifies a specific error arising during type checking, along
+/// with the heap index of the offending term.
+#[derive(Clone,Debug,PartialEq)]
+pub struct TypeError {
+    /// Index of the term at which the error arose.
+    pub index: usize,
+    /// Human-readable description of the error.
+    pub message: String
+}
+
+impl TypeError {
+    pub fn new(index: usize, message: String) -> Self {
+        Self{index,message}
+    }
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for TypeError {
+
+}
+
+// ===================================================================
+// Typing
+// ===================================================================
+
+/// Records the type determined for each term in the heap.  Terms
+/// which are not expressions or statements (e.g. types) have no type.
+pub struct Typing {
+    types: Vec<Option<Type>>,
+    /// Signatures of declared functions (i.e. parameter and return
+    /// types).
+    functions: HashMap<String,(Vec<Type>,Type)>
+}
+
+impl Typing {
+    /// Get the type of the term at a given index (if it has one).
+    pub fn get(&self, index: usize) -> Option<&Type> {
+        self.types.get(index).and_then(|t| t.as_ref())
+    }
+
+    /// Get the type of the expression at a given index.  This assumes
+    /// the term at that index was successfully type checked.
+    pub fn type_of(&self, index: usize) -> &Type {
+        self.get(index).unwrap()
+    }
+
+    /// Get the parameter and return types of a given function (if it
+    /// was declared).
+    pub fn signature(&self, name: &str) -> Option<&(Vec<Type>,Type)> {
+        self.functions.get(name)
+    }
+}
+
+// ===================================================================
+// Type Checker
+// ===================================================================
+
+/// Responsible for checking that a given program is well-typed, and
+/// determining the type of every expression within it.
+pub struct TypeChecker<'a> {
+    heap: &'a SyntacticHeap,
+    /// Signatures of declared functions (i.e. parameter and return
+    /// types).
+    functions: HashMap<String,(Vec<Type>,Type)>,
+    /// Types of variables currently in scope.
+    env: HashMap<String,Type>,
+    /// Types determined so far.
+    types: Vec<Option<Type>>
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(heap: &'a SyntacticHeap) -> Self {
+        let types = vec![None; heap.len()];
+        Self{heap, functions: HashMap::new(), env: HashMap::new(), types}
+    }
+
+    /// Type check a given set of top-level declarations, producing
+    /// the type of every term within them.
+    pub fn check(mut self, declarations: &[usize]) -> Result<Typing,TypeError> {
+        // Register all function signatures first, to allow recursive
+        // and forward references.
+        for d in declarations {
+            if let Term::Function(fun) = self.heap.get(*d) {
+                if self.functions.contains_key(&fun.name) {
+                    let msg = format!("function `{}` already declared",fun.name);
+                    return Err(TypeError::new(*d,msg));
+                }
+                let params = fun.params.iter().map(|p| Type::from_heap(self.heap,p.0)).collect();
+                let ret = Self::return_type(self.heap,fun);
+                self.functions.insert(fun.name.clone(),(params,ret));
+            }
+        }
+        // Check each declaration in turn
+        for d in declarations {
+            self.check_term(*d)?;
+        }
+        Ok(Typing{types: self.types, functions: self.functions})
+    }
+
+    /// Determine the return type of a function.  A function with no
+    /// returns has type `void`, and a function with multiple returns
+    /// returns a tuple.
+    pub fn return_type(heap: &SyntacticHeap, fun: &Function) -> Type {
+        match fun.rets.len() {
+            0 => Type::Void,
+            1 => Type::from_heap(heap,fun.rets[0].0),
+            _ => Type::Tuple(fun.rets.iter().map(|r| Type::from_heap(heap,r.0)).collect())
+        }
+    }
+
+    // ===============================================================
+    // Internal
+    // ===============================================================
+
+    fn check_term(&mut self, index: usize) -> Result<Type,TypeError> {
+        let t = match self.heap.get(index) {
+            // Declarations
+            Term::Function(fun) => self.check_decl_function(fun)?,
+            // Statements
+            Term::Assert(e) => self.check_stmt_assert(*e)?,
+            Term::Assume(e) => self.check_stmt_assert(*e)?,
+            Term::Block(terms) => self.check_stmt_block(terms)?,
+            Term::Let(var,e) => self.check_stmt_let(var,*e)?,
+            Term::LetTuple(vars,e) => self.check_stmt_lettuple(vars,*e)?,
+            // Expressions
+            Term::ArrayAccess{src,index} => self.check_expr_arrayaccess(*src,*index)?,
+            Term::ArrayGenerator(item,len) => self.check_expr_arraygenerator(*item,*len)?,
+            Term::ArraySlice{src,start,end} => self.check_expr_arrayslice(*src,*start,*end)?,
+            Term::ArrayLength(src) => self.check_expr_arraylength(*src)?,
+            Term::ArrayConstructor(vs) => self.check_expr_arrayconstructor(index,vs)?,
+            Term::Binary(bop,lhs,rhs) => self.check_expr_binary(index,*bop,*lhs,*rhs)?,
+            Term::BoolLiteral(_) => Type::Bool,
+            Term::Braced(e) => self.check_term(*e)?,
+            Term::IntLiteral(_) => Type::Int(false),
+            Term::IfElse{cond,tt,ff} => self.check_expr_ifelse(index,*cond,*tt,*ff)?,
+            Term::VarAccess(v) => self.check_expr_varaccess(index,v)?,
+            Term::StaticInvoke(name,args) => self.check_expr_invoke(index,name,args)?,
+            Term::TupleAccess(src,i) => self.check_expr_tupleaccess(index,*src,*i)?,
+            Term::TupleConstructor(vs) => self.check_expr_tupleconstructor(vs)?,
+            Term::Unary(uop,src) => self.check_expr_unary(*uop,*src)?,
+            // Types
+            t => { unreachable!("unexpected term {t:?}") }
+        };
+        self.types[index] = Some(t.clone());
+        Ok(t)
+    }
+
+    // ===============================================================
+    // Declarations
+    // ===============================================================
+
+    fn check_decl_function(&mut self, fun: &Function) -> Result<Type,TypeError> {
+        self.env.clear();
+        // Declare parameters
+        for (t,n) in &fun.params {
+            self.env.insert(n.clone(),Type::from_heap(self.heap,*t));
+        }
+        // Check preconditions
+        for r in &fun.requires {
+            self.check_expected(*r,&Type::Bool)?;
+        }
+        // Check body
+        let ret = Self::return_type(self.heap,fun);
+        if ret == Type::Void {
+            self.check_term(fun.body)?;
+        } else {
+            self.check_expected(fun.body,&ret)?;
+        }
+        // Declare returns
+        for (t,n) in &fun.rets {
+            self.env.insert(n.clone(),Type::from_heap(self.heap,*t));
+        }
+        // Check postconditions
+        for e in &fun.ensures {
+            self.check_expected(*e,&Type::Bool)?;
+        }
+        Ok(Type::Void)
+    }
+
+    // ===============================================================
+    // Statements
+    // ===============================================================
+
+    fn check_stmt_assert(&mut self, expr: usize) -> Result<Type,TypeError> {
+        self.check_expected(expr,&Type::Bool)?;
+        Ok(Type::Void)
+    }
+
+    /// The type of a block is that of its trailing expression, or
+    /// `void` if it has none.  Variables declared within a block are
+    /// not visible outside it.
+    fn check_stmt_block(&mut self, terms: &[usize]) -> Result<Type,TypeError> {
+        let env = self.env.clone();
+        let mut t = Type::Void;
+        for term in terms {
+            t = self.check_term(*term)?;
+        }
+        self.env = env;
+        Ok(t)
+    }
+
+    fn check_stmt_let(&mut self, var: &str, expr: usize) -> Result<Type,TypeError> {
+        let t = self.check_term(expr)?;
+        if t == Type::Void {
+            let msg = format!("cannot bind `{var}` to statement");
+            return Err(TypeError::new(expr,msg));
+        }
+        self.env.insert(var.to_string(),t);
+        Ok(Type::Void)
+    }
+
+    /// For `let (x1,..,xn) = e`, the expression `e` must be a tuple
+    /// with exactly `n` components.
+    fn check_stmt_lettuple(&mut self, vars: &[String], expr: usize) -> Result<Type,TypeError> {
+        let ts = match self.check_term(expr)? {
+            Type::Tuple(ts) if ts.len() == vars.len() => ts,
+            t => {
+                let msg = format!("cannot destructure `{t}` into {} variable(s)",vars.len());
+                return Err(TypeError::new(expr,msg));
+            }
+        };
+        for (var,t) in vars.iter().zip(ts) {
+            self.env.insert(var.clone(),t);
+        }
+        Ok(Type::Void)
+    }
+
+    // ===============================================================
+    // Expressions
+    // ===============================================================
+
+    fn check_expr_arrayaccess(&mut self, src: usize, index: usize) -> Result<Type,TypeError> {
+        let t = self.check_array(src)?;
+        self.check_int(index)?;
+        Ok(t)
+    }
+
+    fn check_expr_arraygenerator(&mut self, item: usize, len: usize) -> Result<Type,TypeError> {
+        let t = self.check_term(item)?;
+        self.check_int(len)?;
+        Ok(Type::Array(Box::new(t)))
+    }
+
+    fn check_expr_arrayslice(&mut self, src: usize, start: usize, end: usize) -> Result<Type,TypeError> {
+        let t = self.check_array(src)?;
+        self.check_int(start)?;
+        self.check_int(end)?;
+        Ok(Type::Array(Box::new(t)))
+    }
+
+    fn check_expr_arraylength(&mut self, src: usize) -> Result<Type,TypeError> {
+        self.check_array(src)?;
+        Ok(Type::Int(false))
+    }
+
+    fn check_expr_arrayconstructor(&mut self, index: usize, terms: &[usize]) -> Result<Type,TypeError> {
+        let mut t = Type::Bottom;
+        for term in terms {
+            let ith = self.check_term(*term)?;
+            t = match t.join(&ith) {
+                Some(t) => t,
+                None => {
+                    let msg = format!("incompatible array elements `{t}` and `{ith}`");
+                    return Err(TypeError::new(index,msg));
+                }
+            };
+        }
+        for term in terms {
+            self.refine(*term,&t);
+        }
+        Ok(Type::Array(Box::new(t)))
+    }
+
+    fn check_expr_binary(&mut self, index: usize, bop: BinOp, lhs: usize, rhs: usize) -> Result<Type,TypeError> {
+        match bop {
+            // Arithmetic
+            BinOp::Add|BinOp::Subtract|BinOp::Multiply|BinOp::Divide|BinOp::Remainder => {
+                let l = self.check_int(lhs)?;
+                let r = self.check_int(rhs)?;
+                Ok(Type::Int(l || r))
+            }
+            // Comparators
+            BinOp::LessThan|BinOp::LessThanOrEquals|BinOp::GreaterThan|BinOp::GreaterThanOrEquals => {
+                self.check_int(lhs)?;
+                self.check_int(rhs)?;
+                Ok(Type::Bool)
+            }
+            // Equality
+            BinOp::Equals|BinOp::NotEquals => {
+                let l = self.check_term(lhs)?;
+                let r = self.check_term(rhs)?;
+                match l.join(&r) {
+                    Some(t) => {
+                        self.refine(lhs,&t);
+                        self.refine(rhs,&t);
+                    }
+                    None => {
+                        let msg = format!("cannot compare `{l}` with `{r}`");
+                        return Err(TypeError::new(index,msg));
+                    }
+                }
+                Ok(Type::Bool)
+            }
+            // Logical
+            BinOp::LogicalAnd|BinOp::LogicalOr|BinOp::LogicalImplies|BinOp::LogicalIff => {
+                self.check_expected(lhs,&Type::Bool)?;
+                self.check_expected(rhs,&Type::Bool)?;
+                Ok(Type::Bool)
+            }
+        }
+    }
+
+    fn check_expr_ifelse(&mut self, index: usize, cond: usize, tt: usize, ff: usize) -> Result<Type,TypeError> {
+        self.check_expected(cond,&Type::Bool)?;
+        let l = self.check_term(tt)?;
+        let r = self.check_term(ff)?;
+        match l.join(&r) {
+            Some(t) => {
+                self.refine(tt,&t);
+                self.refine(ff,&t);
+                Ok(t)
+            }
+            None => {
+                let msg = format!("incompatible branches `{l}` and `{r}`");
+                Err(TypeError::new(index,msg))
+            }
+        }
+    }
+
+    fn check_expr_varaccess(&mut self, index: usize, var: &str) -> Result<Type,TypeError> {
+        match self.env.get(var) {
+            Some(t) => Ok(t.clone()),
+            None => {
+                let msg = format!("unknown variable `{var}`");
+                Err(TypeError::new(index,msg))
+            }
+        }
+    }
+
+    fn check_expr_invoke(&mut self, index: usize, name: &str, args: &[usize]) -> Result<Type,TypeError> {
+        let (params,ret) = match self.functions.get(name) {
+            Some(sig) => sig.clone(),
+            None => {
+                let msg = format!("unknown function `{name}`");
+                return Err(TypeError::new(index,msg));
+            }
+        };
+        if params.len() != args.len() {
+            let msg = format!("function `{name}` expects {} argument(s), found {}",params.len(),args.len());
+            return Err(TypeError::new(index,msg));
+        }
+        for (arg,param) in args.iter().zip(&params) {
+            self.check_expected(*arg,param)?;
+        }
+        Ok(ret)
+    }
+
+    fn check_expr_tupleaccess(&mut self, index: usize, src: usize, i: usize) -> Result<Type,TypeError> {
+        match self.check_term(src)? {
+            Type::Tuple(ts) if i < ts.len() => Ok(ts[i].clone()),
+            Type::Tuple(ts) => {
+                let msg = format!("tuple index {i} out of range for tuple of size {}",ts.len());
+                Err(TypeError::new(index,msg))
+            }
+            t => {
+                let msg = format!("expected tuple, found `{t}`");
+                Err(TypeError::new(src,msg))
+            }
+        }
+    }
+
+    fn check_expr_tupleconstructor(&mut self, terms: &[usize]) -> Result<Type,TypeError> {
+        let mut ts = Vec::new();
+        for term in terms {
+            ts.push(self.check_term(*term)?);
+        }
+        Ok(Type::Tuple(ts))
+    }
+
+    fn check_expr_unary(&mut self, uop: UnaryOp, src: usize) -> Result<Type,TypeError> {
+        match uop {
+            UnaryOp::Negate => {
+                let signed = self.check_int(src)?;
+                // NOTE: a negated literal (e.g. `-1`) is considered
+                // signed, since otherwise it could only ever be `-0`.
+                let literal = matches!(self.heap.get(src),Term::IntLiteral(_));
+                Ok(Type::Int(signed || literal))
+            }
+            UnaryOp::LogicalNot => {
+                self.check_expected(src,&Type::Bool)?;
+                Ok(Type::Bool)
+            }
+        }
+    }
+
+    // ===============================================================
+    // Helpers
+    // ===============================================================
+
+    /// Check the term at a given index is a subtype of an expected
+    /// type.
+    fn check_expected(&mut self, index: usize, expected: &Type) -> Result<Type,TypeError> {
+        let t = self.check_term(index)?;
+        if !t.is_subtype(expected) {
+            let msg = format!("expected `{expected}`, found `{t}`");
+            return Err(TypeError::new(index,msg));
+        }
+        self.refine(index,expected);
+        Ok(t)
+    }
+
+    /// Refine the type of a term involving `Bottom` to a given
+    /// supertype determined from its context.  For example, the empty
+    /// array `[]` passed as a `uint[]` parameter becomes `uint[]`.
+    /// This ensures such terms can be given a concrete
+    /// representation (e.g. during verification).
+    fn refine(&mut self, index: usize, t: &Type) {
+        match &self.types[index] {
+            Some(old) if old.has_bottom() => {}
+            _ => { return; }
+        }
+        match (self.heap.get(index),t) {
+            (Term::ArrayConstructor(vs),Type::Array(e)) => {
+                for v in vs { self.refine(*v,e); }
+            }
+            (Term::Braced(e),_) => self.refine(*e,t),
+            (Term::IfElse{tt,ff,..},_) => {
+                self.refine(*tt,t);
+                self.refine(*ff,t);
+            }
+            _ => {}
+        }
+        self.types[index] = Some(t.clone());
+    }
+
+    /// Check the term at a given index is an integer, returning
+    /// whether or not it is signed.
+    fn check_int(&mut self, index: usize) -> Result<bool,TypeError> {
+        match self.check_term(index)? {
+            Type::Int(s) => Ok(s),
+            t => {
+                let msg = format!("expected integer, found `{t}`");
+                Err(TypeError::new(index,msg))
+            }
+        }
+    }
+
+    /// Check the term at a given index is an array, returning its
+    /// element type.
+    fn check_array(&mut self, index: usize) -> Result<Type,TypeError> {
+        match self.check_term(index)? {
+            Type::Array(e) => Ok(*e),
+            t => {
+                let msg = format!("expected array, found `{t}`");
+                Err(TypeError::new(index,msg))
+            }
+        }
+    }
+}
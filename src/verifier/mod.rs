@@ -1,9 +1,11 @@
 mod vcg;
+mod recursion;
 mod translator;
 
 use std::collections::{HashMap};
-use crate::circuit::{Circuit,Function};
+use crate::circuit::Circuit;
 pub use vcg::*;
+pub use recursion::*;
 
 
 pub struct Environment<C:Circuit> {
@@ -23,10 +25,29 @@ impl<C:Circuit> Environment<C> {
     pub fn lookup(&self, name: &str) -> &C::Term {
         self.bindings.get(name).unwrap()
     }
-    pub fn declare_fn(&mut self, decl: C::Function) {
-        self.fn_bindings.insert(decl.name(), decl);
+    /// Take a copy of the current variable bindings, such that they
+    /// can be restored on leaving a scope.
+    pub fn snapshot(&self) -> HashMap<String, C::Term> {
+        self.bindings.clone()
     }
-    pub fn lookup_fn(&self, name: &str) -> &C::Function {
-        self.fn_bindings.get(name).unwrap()
+    pub fn restore(&mut self, bindings: HashMap<String, C::Term>) {
+        self.bindings = bindings;
+    }
+    /// Bind a given (source-level) function name to its declaration.
+    /// Observe the declaration may have a different name within the
+    /// circuit (e.g. if the source-level name was reserved).
+    pub fn declare_fn(&mut self, name: &str, decl: C::Function) {
+        self.fn_bindings.insert(name.to_string(), decl);
+    }
+    /// Lookup the declaration bound to a given (source-level)
+    /// function name.  Functions without returns have no declaration.
+    pub fn lookup_fn(&self, name: &str) -> Option<&C::Function> {
+        self.fn_bindings.get(name)
+    }
+}
+
+impl<C:Circuit> Default for Environment<C> {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,255 @@
+use std::collections::{HashMap,HashSet};
+use crate::{BinOp,Function,SyntacticHeap,Term,UnaryOp};
+
+// =============================================================================
+// Unchecked Recursion
+// =============================================================================
+
+/// Determine which functions (amongst the given top-level
+/// declarations) are recursive, but cannot be shown to terminate.
+/// The heap index of each such function is returned (in order of
+/// declaration).
+///
+/// A recursive function is shown to terminate when it recurses only
+/// directly (i.e. not through other functions), and has a _measure_.
+/// That is a parameter which every recursive call moves strictly
+/// towards a bound established by the enclosing conditions.  For
+/// example, `x` is a measure for `if x > 0 { f(x - 1) } else { 0 }`.
+/// Likewise, an array parameter `xs` is a measure when every call
+/// passes a slice `xs[k..]` (with `k >= 1`) and `xs` is known to be
+/// non-empty.  Bounds may refer only to parameters which every
+/// recursive call passes unchanged.
+///
+/// Exposing the body of a non-terminating function to its callers is
+/// unsound, since equating a call with its body can give a
+/// contradiction (e.g. `f(0) == f(0) + 1`).
+pub fn unchecked_recursion(heap: &SyntacticHeap, declarations: &[usize]) -> Vec<usize> {
+    // Determine calls made by each function
+    let mut calls = HashMap::new();
+    for index in declarations {
+        if let Term::Function(f) = heap.get(*index) {
+            let mut callees = HashSet::new();
+            callees_of(heap,f.body,&mut callees);
+            calls.insert(f.name.as_str(),callees);
+        }
+    }
+    // Identify recursive functions without a measure
+    declarations.iter().copied().filter(|index| {
+        match heap.get(*index) {
+            Term::Function(f) => {
+                let callees = &calls[f.name.as_str()];
+                let mutual = callees.iter().any(|g| *g != f.name && reaches(&calls,g,&f.name));
+                mutual || (callees.contains(f.name.as_str()) && !has_measure(heap,f))
+            }
+            _ => false
+        }
+    }).collect()
+}
+
+/// Collect the names of all functions invoked by a given term.
+fn callees_of<'a>(heap: &'a SyntacticHeap, index: usize, callees: &mut HashSet<&'a str>) {
+    if let Term::StaticInvoke(name,_) = heap.get(index) {
+        callees.insert(name.as_str());
+    }
+    for child in heap.get(index).children() {
+        callees_of(heap,child,callees);
+    }
+}
+
+/// Determine whether a given function can be reached from `from` by
+/// following one or more calls.
+fn reaches(calls: &HashMap<&str,HashSet<&str>>, from: &str, target: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut worklist = vec![from];
+    while let Some(f) = worklist.pop() {
+        for g in calls.get(f).into_iter().flatten() {
+            if *g == target { return true; }
+            if visited.insert(*g) { worklist.push(*g); }
+        }
+    }
+    false
+}
+
+// =============================================================================
+// Measures
+// =============================================================================
+
+/// Represents a recursive call, along with the conditions (and their
+/// polarity) of the branches within which it is made.
+type Call = (Vec<usize>,Vec<(usize,bool)>);
+
+/// Determine whether some parameter of a (directly) recursive
+/// function is a measure for all of its recursive calls.
+fn has_measure(heap: &SyntacticHeap, fun: &Function) -> bool {
+    let mut calls = Vec::new();
+    recursive_calls(heap,&fun.name,fun.body,&mut Vec::new(),&mut calls);
+    // Parameters rebound in the body cannot be measures or bounds
+    let mut rebound = HashSet::new();
+    rebound_vars(heap,fun.body,&mut rebound);
+    let params : Vec<&str> = fun.params.iter().map(|p| p.1.as_str()).collect();
+    let invariant : Vec<&str> = params.iter().enumerate().filter(|(i,p)| {
+        !rebound.contains(**p) && calls.iter().all(|(args,_)| is_var(heap,args[*i],p))
+    }).map(|(_,p)| *p).collect();
+    //
+    params.iter().enumerate().any(|(i,p)| {
+        let array = matches!(heap.get(fun.params[i].0),Term::ArrayType(_));
+        !rebound.contains(*p) && [BinOp::Subtract,BinOp::Add].iter().any(|dir| {
+            calls.iter().all(|(args,path)| {
+                let facts = facts_of(heap,path);
+                if array {
+                    // Slicing shrinks a non-empty array
+                    *dir == BinOp::Subtract && is_slice(heap,args[i],p) && facts.iter().any(|f| is_nonempty(heap,*f,p))
+                } else {
+                    is_step(heap,args[i],*dir,p) && facts.iter().any(|f| is_bound(heap,*f,*dir,p,&invariant))
+                }
+            })
+        })
+    })
+}
+
+/// Collect all calls to a given function within a term, along with
+/// the conditions under which each is made.
+fn recursive_calls(heap: &SyntacticHeap, name: &str, index: usize, path: &mut Vec<(usize,bool)>, calls: &mut Vec<Call>) {
+    match heap.get(index) {
+        Term::IfElse{cond,tt,ff} => {
+            recursive_calls(heap,name,*cond,path,calls);
+            path.push((*cond,true));
+            recursive_calls(heap,name,*tt,path,calls);
+            path.pop();
+            path.push((*cond,false));
+            recursive_calls(heap,name,*ff,path,calls);
+            path.pop();
+        }
+        term => {
+            if let Term::StaticInvoke(n,args) = term {
+                if n == name { calls.push((args.clone(),path.clone())); }
+            }
+            for child in term.children() {
+                recursive_calls(heap,name,child,path,calls);
+            }
+        }
+    }
+}
+
+/// Collect all variables declared (via `let`) within a term.
+fn rebound_vars<'a>(heap: &'a SyntacticHeap, index: usize, vars: &mut HashSet<&'a str>) {
+    match heap.get(index) {
+        Term::Let(v,_) => { vars.insert(v.as_str()); }
+        Term::LetTuple(vs,_) => { vars.extend(vs.iter().map(|v| v.as_str())); }
+        _ => {}
+    }
+    for child in heap.get(index).children() {
+        rebound_vars(heap,child,vars);
+    }
+}
+
+/// Break down a set of branch conditions into the comparisons known
+/// to hold.  Each is normalised so that it holds positively (e.g.
+/// `!(x <= 0)` gives `x > 0`).
+fn facts_of(heap: &SyntacticHeap, path: &[(usize,bool)]) -> Vec<(usize,BinOp,usize)> {
+    let mut facts = Vec::new();
+    let mut worklist = path.to_vec();
+    while let Some((index,sign)) = worklist.pop() {
+        match (heap.get(index),sign) {
+            (Term::Braced(e),_) => worklist.push((*e,sign)),
+            (Term::Unary(UnaryOp::LogicalNot,e),_) => worklist.push((*e,!sign)),
+            (Term::Binary(BinOp::LogicalAnd,l,r),true)|(Term::Binary(BinOp::LogicalOr,l,r),false) => {
+                worklist.push((*l,sign));
+                worklist.push((*r,sign));
+            }
+            (Term::Binary(bop,l,r),true) => facts.push((*l,*bop,*r)),
+            (Term::Binary(bop,l,r),false) => {
+                let bop = match bop {
+                    BinOp::Equals => BinOp::NotEquals,
+                    BinOp::NotEquals => BinOp::Equals,
+                    BinOp::LessThan => BinOp::GreaterThanOrEquals,
+                    BinOp::LessThanOrEquals => BinOp::GreaterThan,
+                    BinOp::GreaterThan => BinOp::LessThanOrEquals,
+                    BinOp::GreaterThanOrEquals => BinOp::LessThan,
+                    _ => { continue; }
+                };
+                facts.push((*l,bop,*r));
+            }
+            _ => {}
+        }
+    }
+    facts
+}
+
+/// Determine whether a given argument is `p - k` (or `p + k`) for
+/// some literal `k >= 1`.
+fn is_step(heap: &SyntacticHeap, arg: usize, dir: BinOp, p: &str) -> bool {
+    match heap.get(strip(heap,arg)) {
+        Term::Binary(bop,l,r) if *bop == dir => {
+            let step = |v,k| is_var(heap,v,p) && matches!(heap.get(strip(heap,k)),Term::IntLiteral(k) if *k >= 1);
+            step(*l,*r) || (dir == BinOp::Add && step(*r,*l))
+        }
+        _ => false
+    }
+}
+
+/// Determine whether a given fact bounds `p` from below (when
+/// decreasing) or above (when increasing) by an expression which is
+/// invariant across recursive calls.
+fn is_bound(heap: &SyntacticHeap, (l,bop,r): (usize,BinOp,usize), dir: BinOp, p: &str, invariant: &[&str]) -> bool {
+    let (lower,upper) = match bop {
+        BinOp::GreaterThan|BinOp::GreaterThanOrEquals => ((l,r),(r,l)),
+        BinOp::LessThan|BinOp::LessThanOrEquals => ((r,l),(l,r)),
+        _ => { return false; }
+    };
+    // Identify the side which must be `p`
+    let (v,bound) = if dir == BinOp::Subtract { lower } else { upper };
+    is_var(heap,v,p) && is_invariant(heap,bound,invariant)
+}
+
+/// Determine whether a given argument is a slice `p[k..e]` for some
+/// literal `k >= 1`.
+fn is_slice(heap: &SyntacticHeap, arg: usize, p: &str) -> bool {
+    match heap.get(strip(heap,arg)) {
+        Term::ArraySlice{src,start,..} => {
+            is_var(heap,*src,p) && matches!(heap.get(strip(heap,*start)),Term::IntLiteral(k) if *k >= 1)
+        }
+        _ => false
+    }
+}
+
+/// Determine whether a given fact implies the array `p` is non-empty
+/// (e.g. `p != []` or `|p| > 0`).
+fn is_nonempty(heap: &SyntacticHeap, (l,bop,r): (usize,BinOp,usize), p: &str) -> bool {
+    let is_empty = |i| matches!(heap.get(strip(heap,i)),Term::ArrayConstructor(vs) if vs.is_empty());
+    let is_len = |i| matches!(heap.get(strip(heap,i)),Term::ArrayLength(src) if is_var(heap,*src,p));
+    let is_lit = |i,n| matches!(heap.get(strip(heap,i)),Term::IntLiteral(k) if *k >= n);
+    match bop {
+        BinOp::NotEquals => (is_var(heap,l,p) && is_empty(r)) || (is_empty(l) && is_var(heap,r,p)),
+        BinOp::GreaterThan => is_len(l) && is_lit(r,0),
+        BinOp::GreaterThanOrEquals => is_len(l) && is_lit(r,1),
+        BinOp::LessThan => is_lit(l,0) && is_len(r),
+        BinOp::LessThanOrEquals => is_lit(l,1) && is_len(r),
+        _ => false
+    }
+}
+
+/// Determine whether an expression depends only on literals and
+/// invariant parameters.
+fn is_invariant(heap: &SyntacticHeap, index: usize, invariant: &[&str]) -> bool {
+    match heap.get(index) {
+        Term::IntLiteral(_) => true,
+        Term::VarAccess(v) => invariant.contains(&v.as_str()),
+        Term::ArrayLength(e)|Term::Braced(e)|Term::Unary(UnaryOp::Negate,e) => is_invariant(heap,*e,invariant),
+        Term::Binary(BinOp::Add|BinOp::Subtract|BinOp::Multiply,l,r) => {
+            is_invariant(heap,*l,invariant) && is_invariant(heap,*r,invariant)
+        }
+        _ => false
+    }
+}
+
+/// Determine whether a given term is an access of variable `p`.
+fn is_var(heap: &SyntacticHeap, index: usize, p: &str) -> bool {
+    matches!(heap.get(strip(heap,index)),Term::VarAccess(v) if v == p)
+}
+
+/// Strip away any braces surrounding a term.
+fn strip(heap: &SyntacticHeap, mut index: usize) -> usize {
+    while let Term::Braced(e) = heap.get(index) { index = *e; }
+    index
+}
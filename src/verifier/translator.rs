@@ -1,21 +1,35 @@
-use crate::circuit::{Any,Bool,Circuit,Function,Int};
-use crate::{BinOp,Environment,SyntacticHeap,Term};
+use std::collections::HashMap;
+use crate::circuit::{Any,Array,Bool,Circuit,Function,Int,Tuple};
+use crate::{BinOp,Environment,SyntacticHeap,Term,Type,Typing,UnaryOp};
 
 use BinOp::*;
 
+/// An invocation of a (source-level) function with some (translated)
+/// arguments.
+pub type Invocation<T> = (String,Vec<T>);
+
 /// Responsible for translating terms in the high-level Abstract
 /// Syntax Tree.
 pub struct Translator<'a, C:Circuit> {
     heap: &'a SyntacticHeap,
-    /// Circuit being constructed.
-    context: &'a C,
+    /// Type of every expression in the source program.
+    typing: &'a Typing,
+    /// Circuit being constructed.  Translation may extend this
+    /// (e.g. with constraints for array generators).
+    context: &'a mut C,
     /// Maps variables from the context.
-    env: &'a Environment<C>
+    env: &'a Environment<C>,
+    /// Maps variables declared within the term being translated
+    /// (e.g. by `let`).  These take precedence over the context.
+    locals: HashMap<String,C::Term>,
+    /// Invocations constructed during translation, along with their
+    /// (translated) arguments.
+    invocations: Vec<Invocation<C::Term>>
 }
 
 impl<'a, C:Circuit> Translator<'a,C> {
-    pub fn new(heap: &'a SyntacticHeap, context: &'a C, env: &'a Environment<C>) -> Self {
-	Self{heap,context,env}
+    pub fn new(heap: &'a SyntacticHeap, typing: &'a Typing, context: &'a mut C, env: &'a Environment<C>) -> Self {
+	Self{heap,typing,context,env,locals: HashMap::new(),invocations: Vec::new()}
     }
 
     // =========================================================================
@@ -33,10 +47,18 @@ impl<'a, C:Circuit> Translator<'a,C> {
             Term::Assert(e) => self.translate_assert(*e),
             Term::Block(stmts) => self.translate_block(stmts),
             // // Expressions
+            Term::ArrayAccess{src,index} => self.translate_array_access(*src,*index),
+            Term::ArrayGenerator(item,len) => self.translate_array_generator(index,*item,*len),
+            Term::ArraySlice{src,start,end} => self.translate_array_slice(*src,*start,*end),
+            Term::ArrayLength(src) => self.translate_array_length(*src),
+            Term::ArrayConstructor(items) => self.translate_array_constructor(index,items),
             Term::Binary(bop,lhs,rhs) => self.translate_binary(*bop,*lhs,*rhs),
             Term::Braced(lhs) => self.translate(*lhs),
             Term::IfElse{cond,tt,ff} => self.translate_ifelse(*cond,*tt,*ff),
             Term::StaticInvoke(n,args) => self.translate_static_invoke(n,args),
+            Term::TupleAccess(src,i) => self.translate_tuple_access(*src,*i),
+            Term::TupleConstructor(items) => self.translate_tuple_constructor(index,items),
+            Term::Unary(uop,src) => self.translate_unary(*uop,*src),
             Term::VarAccess(s) =>  self.translate_var(s),
             // Literals
             Term::BoolLiteral(v) => self.translate_bool_literal(*v),
@@ -48,7 +70,20 @@ impl<'a, C:Circuit> Translator<'a,C> {
     }
 
 
-    pub fn translate_assert(&mut self, index: usize) -> C::Term {
+    /// Bind a given variable to a given term for the purposes of
+    /// translation.  This takes precedence over any binding for the
+    /// same variable in the context.
+    pub fn bind(&mut self, var: &str, value: C::Term) {
+        self.locals.insert(var.to_string(),value);
+    }
+
+    /// Get the (source-level) invocations constructed so far, along
+    /// with their arguments.
+    pub fn invocations(&self) -> &[Invocation<C::Term>] {
+        &self.invocations
+    }
+
+    pub fn translate_assert(&mut self, _index: usize) -> C::Term {
         // Should be a unit term I think?
         self.context.from_bool(false).to_any()
     }
@@ -65,6 +100,12 @@ impl<'a, C:Circuit> Translator<'a,C> {
         C::Int::from_any(&self.translate(index))
     }
 
+    /// Translate the term at a given `index` position within the heap
+    /// into an _array_ AST node.
+    pub fn translate_array(&mut self, index: usize) -> C::Array {
+        C::Array::from_any(&self.translate(index))
+    }
+
     /// Translate the term at a given `index` position within the heap
     /// into a _sort_.  Hence, this assumes the term at `index`
     /// corresponds to a type.
@@ -75,21 +116,132 @@ impl<'a, C:Circuit> Translator<'a,C> {
         let term = self.heap.get(index);
         // Types
         match term {
-            Term::ArrayType(_) => todo!(),
+            Term::ArrayType(e) => {
+                let element = self.translate_type(*e);
+                self.context.array_type(&element)
+            }
             Term::BoolType => self.context.bool_type(),
             Term::IntType(_) => self.context.int_type(),
-            Term::TupleType(_) => todo!(),
+            Term::TupleType(ts) => {
+                let fields : Vec<C::Type> = ts.iter().map(|t| self.translate_type(*t)).collect();
+                self.context.tuple_type(&fields)
+            }
             _ => { unreachable!() }
         }
     }
 
+    /// Translate a type determined by the type checker into a sort.
+    pub fn translate_sort(&mut self, t: &Type) -> C::Type {
+        match t {
+            Type::Array(e) => {
+                let element = self.translate_sort(e);
+                self.context.array_type(&element)
+            }
+            Type::Bool => self.context.bool_type(),
+            Type::Int(_) => self.context.int_type(),
+            // NOTE: terms of type `bottom` only arise when nothing
+            // else is known (e.g. `[] == []`), in which case any sort
+            // will do.
+            Type::Bottom => self.context.int_type(),
+            Type::Tuple(ts) => {
+                let fields : Vec<C::Type> = ts.iter().map(|t| self.translate_sort(t)).collect();
+                self.context.tuple_type(&fields)
+            }
+            // NOTE: there is no unit sort, hence statements are given
+            // the value `true` (see `translate_block()`).
+            Type::Void => self.context.bool_type()
+        }
+    }
+
     // =========================================================================
     // Private Translation Helpers
     // =========================================================================
 
+    fn translate_array_access(&mut self, src: usize, index: usize) -> C::Term {
+        let s = self.translate_array(src);
+        let i = self.translate_int(index);
+        s.get(&i)
+    }
+
+    fn translate_array_generator(&mut self, index: usize, item: usize, len: usize) -> C::Term {
+        let element = self.translate_element_sort(index);
+        let i = self.translate(item);
+        let n = self.translate_int(len);
+        self.context.array_generator(&element,&i,&n).to_any()
+    }
+
+    fn translate_array_slice(&mut self, src: usize, start: usize, end: usize) -> C::Term {
+        let s = self.translate_array(src);
+        let l = self.translate_int(start);
+        let r = self.translate_int(end);
+        s.slice(&l,&r).to_any()
+    }
+
+    fn translate_array_length(&mut self, src: usize) -> C::Term {
+        self.translate_array(src).length().to_any()
+    }
+
+    fn translate_array_constructor(&mut self, index: usize, items: &[usize]) -> C::Term {
+        let element = self.translate_element_sort(index);
+        let terms : Vec<C::Term> = items.iter().map(|i| self.translate(*i)).collect();
+        self.context.array_from(&element,&terms).to_any()
+    }
+
+    /// Determine the element sort of the array expression at a given
+    /// index.
+    fn translate_element_sort(&mut self, index: usize) -> C::Type {
+        match self.typing.type_of(index) {
+            Type::Array(e) => self.translate_sort(e),
+            t => { unreachable!("expected array, found {t}") }
+        }
+    }
+
+    fn translate_tuple_access(&mut self, src: usize, index: usize) -> C::Term {
+        let size = match self.typing.type_of(src) {
+            Type::Tuple(ts) => ts.len(),
+            t => { unreachable!("expected tuple, found {t}") }
+        };
+        C::Tuple::from_any(&self.translate(src)).get(index,size)
+    }
+
+    fn translate_tuple_constructor(&mut self, index: usize, items: &[usize]) -> C::Term {
+        let fields = match self.typing.type_of(index) {
+            Type::Tuple(ts) => ts.iter().map(|t| self.translate_sort(t)).collect::<Vec<_>>(),
+            t => { unreachable!("expected tuple, found {t}") }
+        };
+        let terms : Vec<C::Term> = items.iter().map(|i| self.translate(*i)).collect();
+        self.context.tuple_from(&fields,&terms).to_any()
+    }
+
+    /// Translate a block by threading variables declared within it
+    /// through to its trailing expression, which determines its
+    /// value.  Facts established by statements in the block
+    /// (e.g. `assert`) and any other expression statements are
+    /// accounted for by the verifier.
     fn translate_block(&mut self, indices: &[usize]) -> C::Term {
-        assert_eq!(indices.len(),1,"multi-statement blocks not yet supported");
-        self.translate(indices[0])
+        let locals = self.locals.clone();
+        let mut value = None;
+        for (i,index) in indices.iter().enumerate() {
+            match self.heap.get(*index) {
+                Term::Assert(_)|Term::Assume(_) => {}
+                Term::Let(var,e) => {
+                    let v = self.translate(*e);
+                    self.locals.insert(var.clone(),v);
+                }
+                Term::LetTuple(vars,e) => {
+                    let v = C::Tuple::from_any(&self.translate(*e));
+                    for (i,var) in vars.iter().enumerate() {
+                        self.locals.insert(var.clone(),v.get(i,vars.len()));
+                    }
+                }
+                _ if i + 1 == indices.len() => { value = Some(self.translate(*index)); }
+                _ => {}
+            }
+        }
+        self.locals = locals;
+        // NOTE: blocks without a trailing expression have no value,
+        // and there is no unit sort.  Hence, `true` is used instead.
+        value.unwrap_or_else(|| self.context.from_bool(true).to_any())
     }
 
     // /// Translate an arbitrary binary expression.  This is done by
@@ -109,7 +261,7 @@ impl<'a, C:Circuit> Translator<'a,C> {
                 self.translate_equational(bop,lhs,rhs).to_any()
             }
             // Logic
-            LogicalAnd|LogicalOr|LogicalImplies => {
+            LogicalAnd|LogicalOr|LogicalImplies|LogicalIff => {
                 self.translate_logical(bop,lhs,rhs).to_any()
             }
         }
@@ -168,10 +320,18 @@ impl<'a, C:Circuit> Translator<'a,C> {
             BinOp::LogicalAnd => { l.and(&r) }
             BinOp::LogicalOr => { l.or(&r) }
             BinOp::LogicalImplies => { l.implies(&r) }
+            BinOp::LogicalIff => { l.to_any().eq(&r.to_any()) }
             _ => { unreachable!() }
         }
     }
 
+    fn translate_unary(&mut self, uop: UnaryOp, src: usize) -> C::Term {
+        match uop {
+            UnaryOp::Negate => self.translate_int(src).neg().to_any(),
+            UnaryOp::LogicalNot => self.translate_bool(src).not().to_any()
+        }
+    }
+
     fn translate_ifelse(&mut self, cond: usize, lhs: usize, rhs: usize) -> C::Term {
         let c = self.translate_bool(cond);
         let l = self.translate(lhs);
@@ -180,16 +340,25 @@ impl<'a, C:Circuit> Translator<'a,C> {
     }
 
     fn translate_static_invoke(&mut self, name: &str, args: &[usize]) -> C::Term {
-        // Lookup function to invoke
-        let fun = self.env.lookup_fn(name);
+        // Lookup function to invoke.  Functions without returns are
+        // not declared and, like statements, produce no value.
+        let fun = match self.env.lookup_fn(name) {
+            Some(fun) => fun,
+            None => { return self.context.from_bool(true).to_any(); }
+        };
         // Translate arguments
         let terms : Vec<C::Term> = args.iter().map(|arg| self.translate(*arg)).collect();
         // Construct invocation
-        fun.invoke(&terms)
+        let term = fun.invoke(&terms);
+        self.invocations.push((name.to_string(),terms));
+        term
     }
 
     fn translate_var(&mut self, var: &str) -> C::Term {
-        self.env.lookup(var).clone()
+        match self.locals.get(var) {
+            Some(v) => v.clone(),
+            None => self.env.lookup(var).clone()
+        }
     }
 
     fn translate_bool_literal(&mut self, val: bool) -> C::Term {
@@ -1,9 +1,10 @@
+use std::collections::{HashMap,HashSet};
 use std::fmt;
 use std::error::Error;
-use crate::circuit::{Any,Circuit,Bool,Int};
-use crate::{BinOp,Function,SyntacticHeap,Term};
-use super::Environment;
-use super::translator::Translator;
+use crate::circuit::{Any,Array,Circuit,Bool,Function as _,Int,Tuple,VcDescriptor,VcKind};
+use crate::{BinOp,Function,SyntacticHeap,Term,Type,Typing,UnaryOp};
+use super::{Environment,unchecked_recursion};
+use super::translator::{Invocation,Translator};
 
 // =============================================================================
 // Verifier Error
@@ -29,6 +30,34 @@ impl Error for VerifierError {
 
 }
 
+// =============================================================================
+// Definitions
+// =============================================================================
+
+/// Determines whether (and how) the bodies of functions are exposed
+/// to their callers.  By default, callers see only the contract of a
+/// function.  Exposing bodies allows more properties to be proven,
+/// but is only sound for functions which terminate.  Hence, recursive
+/// functions which cannot be shown to terminate (see
+/// `unchecked_recursion()`) are never exposed.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum Definitions {
+    /// Functions are uninterpreted, such that callers see only their
+    /// contracts.
+    #[default]
+    Opaque,
+    /// Functions are defined (recursively) in the circuit, which
+    /// requires a solver supporting recursive definitions.
+    Recursive,
+    /// Function bodies are unrolled at each call site, with nested
+    /// calls unrolled up to a given depth (the _fuel_).
+    Unroll(usize)
+}
+
+// =============================================================================
+// Verifier
+// =============================================================================
+
 /// Responsible for generating verification conditions necessary to
 /// ensure that a given term is _well-defined_ or not.  For example,
 /// consider the following statement:
@@ -57,16 +86,33 @@ impl Error for VerifierError {
 pub struct Verifier<'a, C:Circuit> {
     /// Represents the original source program being verified.
     heap: &'a SyntacticHeap,
+    /// Type of every expression in the source program.
+    typing: &'a Typing,
     /// The verification circuit being constructed.
     circuit: C,
     /// Name resolver
-    env: Environment<C>
+    env: Environment<C>,
+    /// Source-level declarations of all functions, from which
+    /// contracts are instantiated at call sites.
+    functions: HashMap<String,&'a Function>,
+    /// Determines whether function bodies are exposed to callers.
+    definitions: Definitions,
+    /// Functions whose bodies cannot be exposed, since they may not
+    /// terminate.
+    unchecked: HashSet<String>
 }
 
 impl<'a, C:Circuit> Verifier<'a,C> {
-    pub fn new(heap: &'a SyntacticHeap, circuit: C) -> Self {
+    pub fn new(heap: &'a SyntacticHeap, typing: &'a Typing, circuit: C) -> Self {
 	let env = Environment::new();
-        Self{heap, env, circuit}
+        Self{heap, typing, env, circuit, functions: HashMap::new(), definitions: Definitions::Opaque, unchecked: HashSet::new()}
+    }
+
+    /// Determine whether (and how) function bodies are exposed to
+    /// callers.
+    pub fn with_definitions(mut self, definitions: Definitions) -> Self {
+        self.definitions = definitions;
+        self
     }
 
     /// Generate a circuit (i.e. a set of verification conditions) for
@@ -74,6 +120,28 @@ impl<'a, C:Circuit> Verifier<'a,C> {
     pub fn to_circuit(mut self, declarations: &[usize]) -> Result<C,VerifierError> {
         // Construct initial strongest postcondition.
         let precondition = self.circuit.from_bool(true);
+        // Declare all functions up front, such that they can be
+        // invoked before their declaration (e.g. recursively).
+        for term in declarations {
+            if let Term::Function(fun) = self.heap.get(*term) {
+                self.declare_fn(fun);
+            }
+        }
+        // Identify functions whose bodies cannot be exposed
+        for index in unchecked_recursion(self.heap,declarations) {
+            if let Term::Function(fun) = self.heap.get(index) {
+                self.unchecked.insert(fun.name.clone());
+            }
+        }
+        // Define functions (if requested) once all are declared,
+        // since definitions may be mutually recursive.
+        if self.definitions == Definitions::Recursive {
+            for term in declarations {
+                if let Term::Function(fun) = self.heap.get(*term) {
+                    self.define_fn(fun);
+                }
+            }
+        }
         // Iterate all top-level declarations generating verification
         // conditions as necessary.
         for term in declarations {
@@ -99,8 +167,15 @@ impl<'a, C:Circuit> Verifier<'a,C> {
             Term::Block(terms) => self.generate_stmt_block(terms,precondition),
             Term::Assume(e) => self.generate_stmt_assume(*e,precondition),
             Term::Assert(e) => self.generate_stmt_assert(*e,precondition),
+            Term::Let(var,e) => self.generate_stmt_let(var,*e,precondition),
+            Term::LetTuple(vars,e) => self.generate_stmt_lettuple(vars,*e,precondition),
             // Expressions
-            Term::Binary(bop,lhs,rhs) => self.generate_expr_binary(*bop,*lhs,*rhs,precondition),
+            Term::ArrayAccess{src,index:i} => self.generate_expr_arrayaccess(index,*src,*i,precondition),
+            Term::ArrayGenerator(item,len) => self.generate_expr_arraygenerator(index,*item,*len,precondition),
+            Term::ArraySlice{src,start,end} => self.generate_expr_arrayslice(index,*src,*start,*end,precondition),
+            Term::ArrayLength(src) => self.generate_term(*src,precondition),
+            Term::ArrayConstructor(items) => self.generate_exprs(items,precondition),
+            Term::Binary(bop,lhs,rhs) => self.generate_expr_binary(index,*bop,*lhs,*rhs,precondition),
             Term::Braced(lhs) => self.generate_term(*lhs,precondition),
             Term::IfElse{cond,tt,ff} => self.generate_expr_ifelse(*cond,*tt,*ff,precondition),
             Term::VarAccess(_) =>  {
@@ -108,7 +183,10 @@ impl<'a, C:Circuit> Verifier<'a,C> {
                 // being logically asserted!
         	precondition
             },
-            Term::StaticInvoke(name,args) => self.generate_expr_invoke(&name,args,precondition),
+            Term::StaticInvoke(name,args) => self.generate_expr_invoke(index,name,args,precondition),
+            Term::TupleAccess(src,_) => self.generate_term(*src,precondition),
+            Term::TupleConstructor(items) => self.generate_exprs(items,precondition),
+            Term::Unary(uop,src) => self.generate_expr_unary(index,*uop,*src,precondition),
             // Literals
             Term::BoolLiteral(_) => precondition,
             Term::IntLiteral(_) => precondition,
@@ -128,20 +206,88 @@ impl<'a, C:Circuit> Verifier<'a,C> {
         precondition = self.generate_term(fun.body,precondition);
         // Generate verification conditions for return types
         self.generate_decl_checks(fun,precondition.clone());
-        // Generate (uninterpreted) function declaration
-        let params = self.translate_types(&fun.params);
-        let rets = self.translate_types(&fun.rets);
-        // Declare the function
-        let func = self.circuit.declare_fn(&fun.name,&params,&rets);
-        self.env.declare_fn(func);
-        // //
+        //
         self.circuit.from_bool(true)
     }
 
+    /// Generate an (uninterpreted) function declaration for a given
+    /// function, and record its contract for use at call sites.
+    /// Functions without returns cannot be used within expressions
+    /// and, hence, do not need declaring in the circuit.
+    fn declare_fn(&mut self, fun: &'a Function) {
+        self.functions.insert(fun.name.clone(),fun);
+        //
+        if !fun.rets.is_empty() {
+            let params = self.translate_types(&fun.params);
+            let rets = self.translate_types(&fun.rets);
+            // Declare the function
+            let func = self.circuit.declare_fn(&fun.name,&params,&rets);
+            self.env.declare_fn(&fun.name,func);
+        }
+    }
+
+    /// Define a (previously declared) function in the circuit in
+    /// terms of its body.
+    fn define_fn(&mut self, fun: &Function) {
+        if !self.is_definable(fun) { return; }
+        //
+        let mut params = Vec::new();
+        let mut bindings = Vec::new();
+        for (t,n) in &fun.params {
+            let sort = self.translate_type(*t);
+            let param = self.circuit.declare_param(n,&sort);
+            bindings.push((n.clone(),param.clone()));
+            params.push((param,sort));
+        }
+        let (body,_) = self.translate_body(fun,&bindings);
+        // NOTE: definable functions have returns, hence are declared
+        let func = self.env.lookup_fn(&fun.name).unwrap();
+        self.circuit.define_fn(func,&params,&body);
+    }
+
+    /// Determine whether a function's body can be exposed to its
+    /// callers.  This is not possible for functions without returns,
+    /// which may not terminate, or whose body contains an
+    /// array generator (since generators introduce constants which
+    /// cannot refer to parameters).
+    fn is_definable(&self, fun: &Function) -> bool {
+        if self.unchecked.contains(&fun.name) { return false; }
+        let mut worklist = vec![fun.body];
+        while let Some(index) = worklist.pop() {
+            let term = self.heap.get(index);
+            if matches!(term,Term::ArrayGenerator(..)) { return false; }
+            worklist.extend(term.children());
+        }
+        !fun.rets.is_empty()
+    }
+
+    /// Unroll the body of a function at a given call site, with
+    /// nested calls being unrolled until the fuel is exhausted.  This
+    /// produces a set of facts equating each unrolled call with its
+    /// body.
+    fn unroll(&mut self, name: &str, args: Vec<C::Term>, fuel: usize) -> C::Bool {
+        let mut facts = self.circuit.from_bool(true);
+        let mut worklist = vec![(name.to_string(),args,fuel)];
+        while let Some((name,args,fuel)) = worklist.pop() {
+            let fun = self.functions[&name];
+            if fuel == 0 || !self.is_definable(fun) { continue; }
+            let bindings : Vec<(String,C::Term)> = fun.params.iter().zip(&args).map(|(p,arg)| {
+                (p.1.clone(),arg.clone())
+            }).collect();
+            let (body,calls) = self.translate_body(fun,&bindings);
+            let call = self.env.lookup_fn(&name).unwrap().invoke(&args);
+            facts = facts.and(&call.eq(&body));
+            // Unroll nested calls
+            for (n,a) in calls { worklist.push((n,a,fuel - 1)); }
+        }
+        facts
+    }
+
     fn generate_decl_precondition(&mut self, fun: &Function, mut precondition: C::Bool) -> C::Bool {
-        // Second, extract verification conditions from body.
+        // Declare parameters, assuming their type invariants hold.
         for ith in &fun.params {
-            self.declare(ith.0,&ith.1);
+            let invariant = self.declare(ith.0,&ith.1);
+            precondition = precondition.and(&invariant);
         }
         // Update precondition to include preconditions
         for i in fun.requires.iter() {
@@ -158,20 +304,31 @@ impl<'a, C:Circuit> Verifier<'a,C> {
         // Translate function body
         let body = self.translate(fun.body);
         // Allocate return parameters
-        for ith in &fun.rets {
-            self.declare(ith.0,&ith.1);
+        for (i,ith) in fun.rets.iter().enumerate() {
+            let invariant = self.declare(ith.0,&ith.1);
+            precondition = precondition.and(&invariant);
             let r = self.env.lookup(&ith.1);
-            // NOTE: the following is completely broken for functions
-            // with multiple returns.  At this stage, I don't know how
-            // best to resolve that.
-            precondition = precondition.and(&r.eq(&body));
+            let value = Self::return_value(&body,i,fun.rets.len());
+            precondition = precondition.and(&r.eq(&value));
         }
         // Generate postcondition checks
         for i in fun.ensures.iter() {
             // Translate postcondition
             let ith = self.translate_bool(*i);
             // Emit verification condition
-            self.circuit.assert(precondition.implies(&ith));
+            self.assert(VcKind::Ensures,*i,precondition.implies(&ith));
+        }
+    }
+
+    /// Determine the value of the `i`th return of a function, given
+    /// the value of its body (or of an invocation).  A function with
+    /// multiple returns has a tuple-valued body, whose components are
+    /// bound to its returns in order.
+    fn return_value(value: &C::Term, i: usize, size: usize) -> C::Term {
+        if size == 1 {
+            value.clone()
+        } else {
+            C::Tuple::from_any(value).get(i,size)
         }
     }
 
@@ -179,10 +336,16 @@ impl<'a, C:Circuit> Verifier<'a,C> {
     // Statements
     // ===================================================================================
 
+    /// Facts established by statements within a block (e.g. by
+    /// `assert` or `assume`) hold for the remainder of the block, and
+    /// beyond.  However, variables declared within the block are not
+    /// visible outside it.
     fn generate_stmt_block(&mut self, terms: &[usize], mut precondition: C::Bool) -> C::Bool {
+        let bindings = self.env.snapshot();
         for t in terms {
             precondition = self.generate_term(*t, precondition);
         }
+        self.env.restore(bindings);
         precondition
     }
 
@@ -201,29 +364,134 @@ impl<'a, C:Circuit> Verifier<'a,C> {
         // Translate expression
         let assertion = self.translate_bool(expr);
         // Emit verification condition (i.e. precondition ==> assertion)
-        self.circuit.assert(precondition.implies(&assertion));
+        self.assert(VcKind::Assert,expr,precondition.implies(&assertion));
         // Include assertion as assumption going forward
         precondition.and(&assertion)
     }
 
+    /// For a statement `let x = e`, the variable `x` is simply bound
+    /// to the translation of `e` for the remainder of the enclosing
+    /// block.
+    fn generate_stmt_let(&mut self, var: &str, expr: usize, mut precondition: C::Bool) -> C::Bool {
+        // Extract verification conditions from operand
+        precondition = self.generate_term(expr,precondition);
+        // Translate expression
+        let value = self.translate(expr);
+        // Bind variable
+        self.env.alloc(var,value);
+        //
+        precondition
+    }
+
+    /// For a statement `let (x1,..,xn) = e`, each variable `xi` is
+    /// bound to the `i`th component of `e`.
+    fn generate_stmt_lettuple(&mut self, vars: &[String], expr: usize, mut precondition: C::Bool) -> C::Bool {
+        // Extract verification conditions from operand
+        precondition = self.generate_term(expr,precondition);
+        // Translate expression
+        let value = C::Tuple::from_any(&self.translate(expr));
+        // Bind variables
+        for (i,var) in vars.iter().enumerate() {
+            self.env.alloc(var,value.get(i,vars.len()));
+        }
+        //
+        precondition
+    }
+
     // ===================================================================================
     // Expressions
     // ===================================================================================
 
+    /// Extract verification conditions from a sequence of
+    /// expressions, where each is executed after the last.
+    fn generate_exprs(&mut self, exprs: &[usize], mut precondition: C::Bool) -> C::Bool {
+        for e in exprs {
+            precondition = self.generate_term(*e,precondition);
+        }
+        precondition
+    }
+
+    /// For an expression `xs[i]`, it follows that `0 <= i < |xs|`
+    /// must hold (where `0 <= i` holds trivially for an unsigned
+    /// index).  Furthermore, the element obtained can be assumed to
+    /// satisfy the invariant of its type (e.g. is non-negative for a
+    /// `uint[]`).
+    fn generate_expr_arrayaccess(&mut self, index: usize, src: usize, i: usize, mut precondition: C::Bool) -> C::Bool {
+        // Extract vcs from source and index
+        precondition = self.generate_term(src,precondition);
+        precondition = self.generate_term(i,precondition);
+        // Translate source and index
+        let xs = self.translate_array(src);
+        let j = self.translate_int(i);
+        // Emit verification condition (i.e. 0 <= i < |xs|)
+        let mut bounds = j.lt(&xs.length());
+        if self.typing.type_of(i).is_signed() {
+            let zero = self.circuit.from_usize(0);
+            bounds = zero.lteq(&j).and(&bounds);
+        }
+        self.assert(VcKind::Bounds,index,precondition.implies(&bounds));
+        // Assume element is well-typed
+        let element = self.translate(index);
+        let t = self.typing.type_of(index).clone();
+        precondition.and(&self.type_invariant(&t,&element))
+    }
+
+    /// For an expression `[e;n]`, it follows that `n >= 0` must hold.
+    /// No such condition arises when `n` is unsigned.
+    fn generate_expr_arraygenerator(&mut self, index: usize, item: usize, len: usize, mut precondition: C::Bool) -> C::Bool {
+        // Extract vcs from item and length
+        precondition = self.generate_term(item,precondition);
+        precondition = self.generate_term(len,precondition);
+        // Check whether length is signed
+        if !self.typing.type_of(len).is_signed() {
+            return precondition;
+        }
+        // Translate length
+        let n = self.translate_int(len);
+        let zero = self.circuit.from_usize(0);
+        // Emit verification condition (i.e. n >= 0)
+        self.assert(VcKind::Bounds,index,precondition.implies(&n.gteq(&zero)));
+        // Done
+        precondition
+    }
+
+    /// For an expression `xs[s..e]`, it follows that `0 <= s <= e <=
+    /// |xs|` must hold (where `0 <= s` holds trivially for an
+    /// unsigned start).
+    fn generate_expr_arrayslice(&mut self, index: usize, src: usize, start: usize, end: usize, mut precondition: C::Bool) -> C::Bool {
+        // Extract vcs from source, start and end
+        precondition = self.generate_term(src,precondition);
+        precondition = self.generate_term(start,precondition);
+        precondition = self.generate_term(end,precondition);
+        // Translate source, start and end
+        let xs = self.translate_array(src);
+        let s = self.translate_int(start);
+        let e = self.translate_int(end);
+        // Emit verification condition (i.e. 0 <= s <= e <= |xs|)
+        let mut bounds = s.lteq(&e).and(&e.lteq(&xs.length()));
+        if self.typing.type_of(start).is_signed() {
+            let zero = self.circuit.from_usize(0);
+            bounds = zero.lteq(&s).and(&bounds);
+        }
+        self.assert(VcKind::Bounds,index,precondition.implies(&bounds));
+        // Done
+        precondition
+    }
+
     /// Extract verification conditions from a binary expression.
     /// Whilst some binary operators (e.g. `/`) generate verification
     /// conditions, most don't.  In all cases, we must recursively
     /// generate verification conditions for the operands.
-    fn generate_expr_binary(&mut self, bop: BinOp, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
+    fn generate_expr_binary(&mut self, index: usize, bop: BinOp, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
         match bop {
             //
             BinOp::LogicalAnd => self.generate_expr_and(lhs,rhs,precondition),
             BinOp::LogicalOr => self.generate_expr_or(lhs,rhs,precondition),
             BinOp::LogicalImplies => self.generate_expr_implies(lhs,rhs,precondition),
             //
-            BinOp::Subtract => self.generate_expr_sub(lhs,rhs,precondition),
-            BinOp::Divide => self.generate_expr_div(lhs,rhs,precondition),
-            BinOp::Remainder => self.generate_expr_rem(lhs,rhs,precondition),
+            BinOp::Subtract => self.generate_expr_sub(index,lhs,rhs,precondition),
+            BinOp::Divide => self.generate_expr_div(index,lhs,rhs,precondition),
+            BinOp::Remainder => self.generate_expr_rem(index,lhs,rhs,precondition),
             //
             _ => {
                 precondition = self.generate_term(lhs,precondition);
@@ -233,6 +501,23 @@ impl<'a, C:Circuit> Verifier<'a,C> {
         }
     }
 
+    /// Extract verification conditions from a unary expression.  For
+    /// an expression `-x` which produces an unsigned integer, it
+    /// follows that `x == 0` must hold.
+    fn generate_expr_unary(&mut self, index: usize, uop: UnaryOp, src: usize, mut precondition: C::Bool) -> C::Bool {
+        // Extract vcs from operand
+        precondition = self.generate_term(src,precondition);
+        //
+        if uop == UnaryOp::Negate && self.typing.type_of(index).is_unsigned() {
+            let s = self.translate(src);
+            let zero = self.circuit.from_usize(0).to_any();
+            // Emit verification condition (i.e. src == 0)
+            self.assert(VcKind::Underflow,index,precondition.implies(&s.eq(&zero)));
+        }
+        // Done
+        precondition
+    }
+
     /// For an expression `e1 && e2` it follows (by short circuiting)
     /// that `e2` is only executed when `e1` is true.  Therefore,
     /// when executing `e2` we can safely assume that `e1` holds.
@@ -288,42 +573,47 @@ impl<'a, C:Circuit> Verifier<'a,C> {
     }
 
     /// For an expression `x - y` which produces an unsigned integer,
-    /// it follows that `x >= y` must hold.
-    fn generate_expr_sub(&mut self, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
+    /// it follows that `x >= y` must hold.  No such condition arises
+    /// when either operand is signed.
+    fn generate_expr_sub(&mut self, index: usize, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
         // Extract vcs from left and right-hand sides
         precondition = self.generate_term(lhs,precondition);
         precondition = self.generate_term(rhs,precondition);
+        // Check whether result is signed
+        if self.typing.type_of(lhs).is_signed() || self.typing.type_of(rhs).is_signed() {
+            return precondition;
+        }
         // Translate left & right-hand sides
         let l = self.translate_int(lhs);
         let r = self.translate_int(rhs);
         // Emit verification condition (i.e. lhs >= rhs)
-        self.circuit.assert(precondition.implies(&l.gteq(&r)));
+        self.assert(VcKind::Underflow,index,precondition.implies(&l.gteq(&r)));
         // Done
         precondition
     }
 
     /// For an expression `x / y`, it follows that `y != 0` must hold.
-    fn generate_expr_div(&mut self, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
+    fn generate_expr_div(&mut self, index: usize, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
         // Extract vcs from left and right-hand sides
         precondition = self.generate_term(lhs,precondition);
         precondition = self.generate_term(rhs,precondition);
         // Translate left & right-hand sides
         let r = self.translate_int(rhs);
         // Emit verification condition (i.e. rhs != 0)
-        self.circuit.assert(precondition.implies(&r.non_zero()));
+        self.assert(VcKind::DivByZero,index,precondition.implies(&r.non_zero()));
         // Done
         precondition
     }
 
     /// For an expression `x % y`, it follows that `y != 0` must hold.
-    fn generate_expr_rem(&mut self, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
+    fn generate_expr_rem(&mut self, index: usize, lhs: usize, rhs: usize, mut precondition: C::Bool) -> C::Bool {
         // Extract vcs from left and right-hand sides
         precondition = self.generate_term(lhs,precondition);
         precondition = self.generate_term(rhs,precondition);
         // Translate left & right-hand sides
         let r = self.translate_int(rhs);
         // Emit verification condition (i.e. rhs != 0)
-        self.circuit.assert(precondition.implies(&r.non_zero()));
+        self.assert(VcKind::DivByZero,index,precondition.implies(&r.non_zero()));
         // Done
         precondition
     }
@@ -340,43 +630,118 @@ impl<'a, C:Circuit> Verifier<'a,C> {
         // Translate condition
         let c = self.translate_bool(cond);
         // Update precondition to include condition.
-        let mut tt_precondition = precondition.and(&c);
-        let mut ff_precondition = precondition.and(&c.not());
+        let tt_precondition = precondition.and(&c);
+        let ff_precondition = precondition.and(&c.not());
         // Extract vcs from left-hand side
-        tt_precondition = self.generate_term(lhs,tt_precondition);
+        let tt_postcondition = self.generate_term(lhs,tt_precondition);
         // Repeate for right-hand side
         // Extract vcs from right-hand side
-        ff_precondition = self.generate_term(rhs,ff_precondition);
-        // FIXME: we should try and merge both precondition.
-        precondition
+        let ff_postcondition = self.generate_term(rhs,ff_precondition);
+        // Merge facts learned on either branch
+        tt_postcondition.or(&ff_postcondition)
     }
 
-    fn generate_expr_invoke(&mut self, _name: &str, args: &[usize], mut precondition: C::Bool) -> C::Bool {
+    /// For an invocation `f(e1,..,en)`, it follows that the
+    /// preconditions of `f` must hold with each parameter bound to
+    /// the corresponding argument.  Furthermore, the postconditions
+    /// of `f` can then be assumed (with the return bound to the
+    /// invocation itself).  Thus, verification is _modular_ as the
+    /// body of `f` is never considered.
+    fn generate_expr_invoke(&mut self, index: usize, name: &str, args: &[usize], mut precondition: C::Bool) -> C::Bool {
         // Generate verification conditions from arguments
         for arg in args {
             precondition = self.generate_term(*arg,precondition);
         }
-        // FIXME: generate verification condition from precondition!
+        // Lookup function being invoked
+        let fun = self.functions[name];
+        // Bind parameters to arguments
+        let mut bindings : Vec<(String,C::Term)> = fun.params.iter().zip(args).map(|(p,arg)| {
+            (p.1.clone(),self.translate(*arg))
+        }).collect();
+        // Generate verification conditions from preconditions
+        for r in &fun.requires {
+            let ith = self.translate_instance(*r,&bindings);
+            // Emit verification condition (against the call site)
+            self.assert(VcKind::Requires,index,precondition.implies(&ith));
+            // Include as assumption going forward
+            precondition = precondition.and(&ith);
+        }
+        // Bind returns (if any) to the invocation
+        if !fun.rets.is_empty() {
+            let result = self.translate(index);
+            // Assume unrolled body (if requested)
+            if let Definitions::Unroll(fuel) = self.definitions {
+                let terms = bindings.iter().map(|b| b.1.clone()).collect();
+                precondition = precondition.and(&self.unroll(name,terms,fuel));
+            }
+            // Bind returns to (components of) the invocation
+            for (i,(t,n)) in fun.rets.iter().enumerate() {
+                let t = Type::from_heap(self.heap,*t);
+                let value = Self::return_value(&result,i,fun.rets.len());
+                precondition = precondition.and(&self.type_invariant(&t,&value));
+                bindings.push((n.clone(),value));
+            }
+        }
+        // Assume postconditions
+        for e in &fun.ensures {
+            let ith = self.translate_instance(*e,&bindings);
+            precondition = precondition.and(&ith);
+        }
+        //
         precondition
     }
 
-    fn translate(&self, term: usize) -> C::Term {
-        let mut translator = Translator::new(self.heap,&self.circuit,&self.env);
+    fn translate(&mut self, term: usize) -> C::Term {
+        let mut translator = Translator::new(self.heap,self.typing,&mut self.circuit,&self.env);
         translator.translate(term)
     }
 
-    fn translate_bool(&self, term: usize) -> C::Bool {
-        let mut translator = Translator::new(self.heap,&self.circuit,&self.env);
+    /// Emit a verification condition of a given kind, arising from a
+    /// given term.
+    fn assert(&mut self, kind: VcKind, index: usize, condition: C::Bool) {
+        self.circuit.assert(VcDescriptor::new(kind,index),condition);
+    }
+
+    fn translate_bool(&mut self, term: usize) -> C::Bool {
+        let mut translator = Translator::new(self.heap,self.typing,&mut self.circuit,&self.env);
+        translator.translate_bool(term)
+    }
+
+    /// Translate a (boolean) term from the contract of some function,
+    /// where its parameters (and returns) are bound to the given
+    /// terms.
+    fn translate_instance(&mut self, term: usize, bindings: &[(String,C::Term)]) -> C::Bool {
+        let mut translator = Translator::new(self.heap,self.typing,&mut self.circuit,&self.env);
+        for (var,value) in bindings {
+            translator.bind(var,value.clone());
+        }
         translator.translate_bool(term)
     }
 
-    fn translate_int(&self, term: usize) -> C::Int {
-        let mut translator = Translator::new(self.heap,&self.circuit,&self.env);
+    /// Translate the body of a function, where its parameters are
+    /// bound to the given terms.  This also returns the invocations
+    /// made within the body.
+    fn translate_body(&mut self, fun: &Function, bindings: &[(String,C::Term)]) -> (C::Term,Vec<Invocation<C::Term>>) {
+        let mut translator = Translator::new(self.heap,self.typing,&mut self.circuit,&self.env);
+        for (var,value) in bindings {
+            translator.bind(var,value.clone());
+        }
+        let body = translator.translate(fun.body);
+        (body,translator.invocations().to_vec())
+    }
+
+    fn translate_int(&mut self, term: usize) -> C::Int {
+        let mut translator = Translator::new(self.heap,self.typing,&mut self.circuit,&self.env);
         translator.translate_int(term)
     }
 
+    fn translate_array(&mut self, term: usize) -> C::Array {
+        let mut translator = Translator::new(self.heap,self.typing,&mut self.circuit,&self.env);
+        translator.translate_array(term)
+    }
+
     /// Translate a sequence of zero or more types.
-    fn translate_types(&self, terms: &[(usize,String)]) -> Vec<C::Type> {
+    fn translate_types(&mut self, terms: &[(usize,String)]) -> Vec<C::Type> {
         let mut r = Vec::new();
         for t in terms {
             r.push(self.translate_type(t.0));
@@ -385,20 +750,58 @@ impl<'a, C:Circuit> Verifier<'a,C> {
     }
 
     /// Translate a given type.
-    fn translate_type(&self, term: usize) -> C::Type {
-        let mut translator = Translator::new(self.heap,&self.circuit,&self.env);
+    fn translate_type(&mut self, term: usize) -> C::Type {
+        let mut translator = Translator::new(self.heap,self.typing,&mut self.circuit,&self.env);
         translator.translate_type(term)
     }
 
-    fn declare(&mut self, type_index: usize, name: &str) {
+    /// Declare a variable of a given type, returning the invariant
+    /// implied by that type (see `type_invariant()`).
+    fn declare(&mut self, type_index: usize, name: &str) -> C::Bool {
         let term = self.heap.get(type_index);
         let v = match term {
             Term::BoolType => self.circuit.declare_bool(name).to_any(),
-            Term::IntType(false) => self.circuit.declare_int(name).to_any(),
+            Term::IntType(_) => self.circuit.declare_int(name).to_any(),
+            Term::ArrayType(e) => {
+                let element = self.translate_type(*e);
+                self.circuit.declare_array(name,&element).to_any()
+            }
+            Term::TupleType(ts) => {
+                let fields : Vec<C::Type> = ts.iter().map(|t| self.translate_type(*t)).collect();
+                self.circuit.declare_tuple(name,&fields).to_any()
+            }
             _ => {
         	todo!()
             }
         };
+        let invariant = self.type_invariant(&Type::from_heap(self.heap,type_index),&v);
         self.env.alloc(name,v);
+        invariant
+    }
+
+    /// Construct the invariant implied by a given type for a given
+    /// term.  Since integers are unbounded in the underlying logic,
+    /// this is necessary to ensure (for example) that a term of type
+    /// `uint` cannot take on negative values.  The invariant of a
+    /// tuple is that of its fields.  The elements of an array are not
+    /// constrained here, as this would require a quantifier.
+    /// Instead, they are constrained as they are accessed.
+    fn type_invariant(&self, t: &Type, term: &C::Term) -> C::Bool {
+        match t {
+            Type::Int(false) => {
+                let zero = self.circuit.from_usize(0);
+                C::Int::from_any(term).gteq(&zero)
+            }
+            Type::Tuple(ts) => {
+                let tuple = C::Tuple::from_any(term);
+                let mut invariant = self.circuit.from_bool(true);
+                for (i,t) in ts.iter().enumerate() {
+                    let field = tuple.get(i,ts.len());
+                    invariant = invariant.and(&self.type_invariant(t,&field));
+                }
+                invariant
+            }
+            _ => self.circuit.from_bool(true)
+        }
     }
 }
@@ -0,0 +1,39 @@
+use tiny_fl::{Diagnostic,Parser,SourceMap,Span};
+
+#[test]
+fn test_line_col_01() {
+    let map = SourceMap::new("ab\ncd\n\nef");
+    assert_eq!(map.line_col(0),(1,1));
+    assert_eq!(map.line_col(1),(1,2));
+    assert_eq!(map.line_col(3),(2,1));
+    assert_eq!(map.line_col(6),(3,1));
+    assert_eq!(map.line_col(8),(4,2));
+    assert_eq!(map.line(2),"cd");
+    assert_eq!(map.line(3),"");
+    assert_eq!(map.num_lines(),4);
+}
+
+#[test]
+fn test_render_01() {
+    let input = "function main() {\n  assert 1 == ;\n}\n";
+    let err = Parser::new(input).parse().unwrap_err();
+    let d = Diagnostic::error(&err.message,Some(err.span()));
+    let expected = "error: expected expression, found `;`\n --> test.tfl:2:15\n  |\n2 |   assert 1 == ;\n  |               ^\n";
+    assert_eq!(d.render("test.tfl",&SourceMap::new(input)),expected);
+}
+
+#[test]
+fn test_render_02() {
+    let input = "function main() {\n\tassert xyz\n}";
+    let d = Diagnostic::warning("unknown variable",Some(Span::new(26,29)));
+    let expected = "warning: unknown variable\n --> test.tfl:2:9\n  |\n2 | \tassert xyz\n  | \t       ^^^\n";
+    assert_eq!(d.render("test.tfl",&SourceMap::new(input)),expected);
+}
+
+#[test]
+fn test_render_03() {
+    let input = "function f(uint x) -> (uint r)\nensures r > x {\n  x\n}";
+    let d = Diagnostic::error("postcondition may not hold",Some(Span::new(39,44))).with_note("counterexample: x = 0");
+    let expected = "error: postcondition may not hold\n --> test.tfl:2:9\n  |\n2 | ensures r > x {\n  |         ^^^^^\n  = note: counterexample: x = 0\n";
+    assert_eq!(d.render("test.tfl",&SourceMap::new(input)),expected);
+}
@@ -0,0 +1,36 @@
+use tiny_fl::{Lexer,TokenType};
+
+/// Extract the token types from the token stream of a given input.
+fn tokens(input: &str, trivia: bool) -> Vec<TokenType> {
+    let lexer = Lexer::new(input);
+    lexer.token_stream(trivia).map(|t| t.unwrap().kind).collect()
+}
+
+#[test]
+fn test_comments_01() {
+    let input = "x // comment\n/* a /* b */ c */ y";
+    assert_eq!(tokens(input,false),vec![TokenType::Identifier,TokenType::Identifier]);
+}
+
+#[test]
+fn test_comments_02() {
+    let input = "x // comment\n/* a /* b */ c */ y";
+    let expected = vec![
+        TokenType::Identifier,
+        TokenType::WhiteSpace,
+        TokenType::LineComment,
+        TokenType::WhiteSpace,
+        TokenType::BlockComment,
+        TokenType::WhiteSpace,
+        TokenType::Identifier
+    ];
+    assert_eq!(tokens(input,true),expected);
+}
+
+#[test]
+fn test_comments_03() {
+    let lexer = Lexer::new("x /* a /* b */");
+    let err = lexer.token_stream(false).find_map(|t| t.err()).unwrap();
+    assert_eq!(err.message,"unterminated block comment");
+    assert_eq!(err.token.offset,2);
+}
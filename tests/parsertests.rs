@@ -0,0 +1,313 @@
+use tiny_fl::{BinOp,ParseError,Parser,Span,SyntacticHeap,Term,TokenType,UnaryOp};
+
+/// Parse a given input and return the parser, along with the indices
+/// of its top-level declarations.
+fn parse(input: &str) -> (Parser,Vec<usize>) {
+    let mut parser = Parser::new(input);
+    let terms = parser.parse().unwrap();
+    (parser,terms)
+}
+
+/// Parse a given expression, and render it back as a string in fully
+/// bracketed form.  This makes the structure determined by the
+/// parser explicit.
+fn bracket(expr: &str) -> String {
+    let (parser,terms) = parse(&format!("function f() {{ {expr} }}"));
+    let body = match parser.heap.get(terms[0]) {
+        Term::Function(f) => f.body,
+        _ => unreachable!()
+    };
+    match parser.heap.get(body) {
+        Term::Block(ts) => to_string(&parser.heap,ts[0]),
+        _ => unreachable!()
+    }
+}
+
+fn to_string(heap: &SyntacticHeap, index: usize) -> String {
+    match heap.get(index) {
+        Term::Binary(bop,l,r) => {
+            let l = to_string(heap,*l);
+            let r = to_string(heap,*r);
+            format!("({l}{}{r})",bop_to_str(*bop))
+        }
+        Term::Braced(t) => to_string(heap,*t),
+        Term::BoolLiteral(b) => b.to_string(),
+        Term::IntLiteral(i) => i.to_string(),
+        Term::VarAccess(v) => v.to_string(),
+        Term::Unary(UnaryOp::Negate,t) => format!("-{}",to_string(heap,*t)),
+        Term::Unary(UnaryOp::LogicalNot,t) => format!("!{}",to_string(heap,*t)),
+        Term::ArrayAccess{src,index} => format!("{}[{}]",to_string(heap,*src),to_string(heap,*index)),
+        t => panic!("unexpected term {t:?}")
+    }
+}
+
+fn bop_to_str(bop: BinOp) -> &'static str {
+    match bop {
+        BinOp::Add => "+",
+        BinOp::Subtract => "-",
+        BinOp::Divide => "/",
+        BinOp::Multiply => "*",
+        BinOp::Remainder => "%",
+        BinOp::Equals => "==",
+        BinOp::NotEquals => "!=",
+        BinOp::LessThan => "<",
+        BinOp::LessThanOrEquals => "<=",
+        BinOp::GreaterThan => ">",
+        BinOp::GreaterThanOrEquals => ">=",
+        BinOp::LogicalAnd => "&&",
+        BinOp::LogicalIff => "<==>",
+        BinOp::LogicalImplies => "==>",
+        BinOp::LogicalOr => "||"
+    }
+}
+
+// ===================================================================
+// Precedence & Associativity
+// ===================================================================
+
+#[test]
+fn test_assoc_01() {
+    assert_eq!(bracket("1 + 2 + 3"),"((1+2)+3)");
+}
+
+#[test]
+fn test_assoc_02() {
+    assert_eq!(bracket("1 - 2 - 3 - 4"),"(((1-2)-3)-4)");
+}
+
+#[test]
+fn test_assoc_03() {
+    assert_eq!(bracket("8 / 4 / 2"),"((8/4)/2)");
+}
+
+#[test]
+fn test_assoc_04() {
+    assert_eq!(bracket("a && b && c"),"((a&&b)&&c)");
+}
+
+#[test]
+fn test_assoc_05() {
+    assert_eq!(bracket("a || b || c"),"((a||b)||c)");
+}
+
+#[test]
+fn test_assoc_06() {
+    assert_eq!(bracket("1 - (2 - 3)"),"(1-(2-3))");
+}
+
+#[test]
+fn test_precedence_01() {
+    assert_eq!(bracket("1 + 2 * 3"),"(1+(2*3))");
+}
+
+#[test]
+fn test_precedence_02() {
+    assert_eq!(bracket("1 * 2 + 3 % 4 - 5 / 6"),"(((1*2)+(3%4))-(5/6))");
+}
+
+#[test]
+fn test_precedence_03() {
+    assert_eq!(bracket("x + 1 < y * 2"),"((x+1)<(y*2))");
+}
+
+#[test]
+fn test_precedence_04() {
+    assert_eq!(bracket("x < y && y <= z"),"((x<y)&&(y<=z))");
+}
+
+#[test]
+fn test_precedence_05() {
+    assert_eq!(bracket("a || b && c"),"(a||(b&&c))");
+}
+
+#[test]
+fn test_precedence_06() {
+    assert_eq!(bracket("a && b || c && d"),"((a&&b)||(c&&d))");
+}
+
+#[test]
+fn test_precedence_07() {
+    assert_eq!(bracket("x == 1 || y != 2 && z >= 3"),"((x==1)||((y!=2)&&(z>=3)))");
+}
+
+#[test]
+fn test_precedence_08() {
+    assert_eq!(bracket("a && b ==> c || d"),"((a&&b)==>(c||d))");
+}
+
+#[test]
+fn test_precedence_09() {
+    assert_eq!(bracket("x > 0 ==> x + 1 > 1"),"((x>0)==>((x+1)>1))");
+}
+
+#[test]
+fn test_precedence_10() {
+    assert_eq!(bracket("a <==> b && c"),"(a<==>(b&&c))");
+}
+
+#[test]
+fn test_assoc_07() {
+    assert_eq!(bracket("a ==> b ==> c"),"(a==>(b==>c))");
+}
+
+#[test]
+fn test_assoc_08() {
+    assert_eq!(bracket("(a ==> b) ==> c"),"((a==>b)==>c)");
+}
+
+#[test]
+fn test_assoc_09() {
+    assert_eq!(bracket("a <==> b ==> c"),"(a<==>(b==>c))");
+}
+
+#[test]
+fn test_assoc_10() {
+    // Equivalence binds more loosely than implication
+    assert_eq!(bracket("a ==> b <==> a"),"((a==>b)<==>a)");
+    assert_eq!(bracket("a <==> b <==> c"),"((a<==>b)<==>c)");
+}
+
+#[test]
+fn test_unary_01() {
+    assert_eq!(bracket("!a && b"),"(!a&&b)");
+}
+
+#[test]
+fn test_unary_02() {
+    assert_eq!(bracket("!(a && b)"),"!(a&&b)");
+}
+
+#[test]
+fn test_unary_03() {
+    assert_eq!(bracket("1 - -x * 2"),"(1-(-x*2))");
+}
+
+#[test]
+fn test_unary_04() {
+    assert_eq!(bracket("--x"),"--x");
+}
+
+#[test]
+fn test_unary_05() {
+    assert_eq!(bracket("!!b != -xs[0]"),"(!!b!=-xs[0])");
+}
+
+// ===================================================================
+// Spans
+// ===================================================================
+
+#[test]
+fn test_span_01() {
+    let input = "function f() -> (uint r) {\n  (1 + xs[0])\n}";
+    let (parser,terms) = parse(input);
+    let heap = &parser.heap;
+    // Function spans the entire input
+    assert_eq!(heap.span(terms[0]),Span::new(0,input.len()));
+    // Body spans the block
+    let body = match heap.get(terms[0]) {
+        Term::Function(f) => f.body,
+        _ => unreachable!()
+    };
+    assert_eq!(heap.span(body),Span::new(25,input.len()));
+    // Drill down into block
+    let braced = match heap.get(body) { Term::Block(ts) => ts[0], _ => unreachable!() };
+    assert_eq!(heap.span(braced),Span::new(29,40));
+    let binary = match heap.get(braced) { Term::Braced(t) => *t, _ => unreachable!() };
+    assert_eq!(heap.span(binary),Span::new(30,39));
+    let access = match heap.get(binary) { Term::Binary(_,_,r) => *r, _ => unreachable!() };
+    assert_eq!(heap.span(access),Span::new(34,39));
+    assert_eq!(heap.to_ref(access).span(),Span::new(34,39));
+}
+
+// ===================================================================
+// Statements
+// ===================================================================
+
+#[test]
+fn test_let_01() {
+    let (parser,terms) = parse("function f() { let x = 1 + 2; x }");
+    let heap = &parser.heap;
+    let body = match heap.get(terms[0]) { Term::Function(f) => f.body, _ => unreachable!() };
+    let stmts = match heap.get(body) { Term::Block(ts) => ts.clone(), _ => unreachable!() };
+    assert_eq!(stmts.len(),2);
+    match heap.get(stmts[0]) {
+        Term::Let(var,e) => {
+            assert_eq!(var,"x");
+            assert_eq!(to_string(heap,*e),"(1+2)");
+        }
+        t => panic!("unexpected term {t:?}")
+    }
+}
+
+#[test]
+fn test_let_03() {
+    let (parser,terms) = parse("function f() { let (x, y) = (1, 2); x }");
+    let heap = &parser.heap;
+    let body = match heap.get(terms[0]) { Term::Function(f) => f.body, _ => unreachable!() };
+    let stmts = match heap.get(body) { Term::Block(ts) => ts.clone(), _ => unreachable!() };
+    match heap.get(stmts[0]) {
+        Term::LetTuple(vars,_) => assert_eq!(vars,&["x","y"]),
+        t => panic!("unexpected term {t:?}")
+    }
+}
+
+#[test]
+fn test_let_02() {
+    let err = Parser::new("function f() { let x == 1 }").parse().unwrap_err();
+    assert_eq!(err.message,"expected `=`, found `==`");
+}
+
+// ===================================================================
+// Errors
+// ===================================================================
+
+/// Parse a given (malformed) input, returning the error reported.
+fn parse_error(input: &str) -> ParseError {
+    match Parser::new(input).parse() {
+        Ok(_) => panic!("expected parse error"),
+        Err(e) => e
+    }
+}
+
+#[test]
+fn test_error_01() {
+    // Missing function name at end of file
+    let e = parse_error("function");
+    assert_eq!(e.span(),Span::new(8,8));
+    assert_eq!(e.expected,vec![TokenType::Identifier]);
+    assert_eq!(e.message,"expected identifier, found end of file");
+}
+
+#[test]
+fn test_error_02() {
+    let e = parse_error("function f() { let = 1 }");
+    assert_eq!(e.span(),Span::new(19,20));
+    assert_eq!(e.expected,vec![TokenType::Identifier]);
+    assert_eq!(e.message,"expected identifier, found `=`");
+}
+
+#[test]
+fn test_error_03() {
+    // Stray closing brace after a declaration
+    let e = parse_error("function f() { x } }");
+    assert_eq!(e.span(),Span::new(19,20));
+    assert_eq!(e.expected,vec![TokenType::Function]);
+    assert_eq!(e.message,"expected `function`, found `}`");
+}
+
+#[test]
+fn test_error_04() {
+    // Missing right-hand operand
+    let e = parse_error("function f() -> (uint r) { 1 + }");
+    assert_eq!(e.span(),Span::new(31,32));
+    assert!(e.expected.is_empty());
+    assert_eq!(e.message,"expected expression, found `}`");
+}
+
+#[test]
+fn test_error_05() {
+    let e = parse_error("function f() { # }");
+    assert_eq!(e.span(),Span::new(15,16));
+    assert!(e.expected.is_empty());
+    assert_eq!(e.message,"unexpected character `#`");
+}
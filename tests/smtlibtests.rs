@@ -0,0 +1,359 @@
+use std::fs;
+use std::path::{Path,PathBuf};
+use std::time::Duration;
+use tiny_fl::{Definitions,Parser,TypeChecker,Verifier};
+use tiny_fl::circuit::{Command,Expr,Limits,Op,Sort,SatResult,SmtError,SmtLibCircuit,SmtLibReader,SmtLibWriter,SmtSession,SmtSolver,SolverProfile,logic,to_model,to_value};
+
+pub static REFTESTS_DIR: &str = "tests/files";
+
+/// Generate the verification conditions for a given test file as a
+/// complete SMT-LIB script (i.e. including a logic).
+fn generate(test: &str) -> Vec<Command> {
+    generate_with(test,Definitions::Opaque)
+}
+
+fn generate_with(test: &str, definitions: Definitions) -> Vec<Command> {
+    let mut path = PathBuf::from(REFTESTS_DIR);
+    path.push(test);
+    let input = fs::read_to_string(path).unwrap();
+    let mut parser = Parser::new(&input);
+    let terms = parser.parse().unwrap();
+    let typing = TypeChecker::new(&parser.heap).check(&terms).unwrap();
+    let smtlib = SmtLibCircuit::new(SmtSolver::new(SolverProfile::Z3,Path::new("z3")));
+    let circuit = Verifier::new(&parser.heap,&typing,smtlib).with_definitions(definitions).to_circuit(&terms).unwrap();
+    let mut script = vec![Command::SetLogic(logic(circuit.commands()))];
+    script.extend_from_slice(circuit.commands());
+    script.push(Command::Exit);
+    script
+}
+
+fn write(commands: &[Command]) -> String {
+    let bytes = SmtLibWriter::new(Vec::new()).write(commands).unwrap();
+    String::from_utf8(bytes).unwrap()
+}
+
+/// Check that writing a given set of commands and reading them back
+/// produces the same commands, and the same text.
+fn check_roundtrip(commands: &[Command]) {
+    let text = write(commands);
+    let read = SmtLibReader::new(&text).read().unwrap();
+    assert_eq!(read,commands);
+    assert_eq!(write(&read),text);
+}
+
+// ===================================================================
+// Writer
+// ===================================================================
+
+#[test]
+fn test_write_01() {
+    let cmds = [Command::DeclareConst("x".to_string(),Sort::Int)];
+    assert_eq!(write(&cmds),"(declare-const x Int)\n");
+}
+
+#[test]
+fn test_write_02() {
+    let cmds = [Command::DeclareFun("f".to_string(),vec![Sort::Int,Sort::Bool],Sort::Int)];
+    assert_eq!(write(&cmds),"(declare-fun f (Int Bool) Int)\n");
+}
+
+#[test]
+fn test_write_03() {
+    let x = Expr::VarAccess("x".to_string());
+    let e = Expr::Operator(Op::Neq,vec![Expr::Operator(Op::Neg,vec![x.clone()]),x]);
+    assert_eq!(write(&[Command::Assert(e)]),"(assert (distinct (- x) x))\n");
+}
+
+#[test]
+fn test_logic_01() {
+    assert_eq!(generate("arith_01.tfl")[0],Command::SetLogic("QF_LIA".to_string()));
+    assert_eq!(generate("assert_01.tfl")[0],Command::SetLogic("QF_UF".to_string()));
+    assert_eq!(generate("invoke_01.tfl")[0],Command::SetLogic("QF_UFLIA".to_string()));
+}
+
+// ===================================================================
+// Round Trip
+// ===================================================================
+
+#[test]
+fn test_roundtrip_01() { check_roundtrip(&generate("arith_01.tfl")); }
+
+#[test]
+fn test_roundtrip_02() { check_roundtrip(&generate("contract_01.tfl")); }
+
+#[test]
+fn test_roundtrip_03() { check_roundtrip(&generate("div_01.tfl")); }
+
+#[test]
+fn test_roundtrip_04() { check_roundtrip(&generate("int_01.tfl")); }
+
+#[test]
+fn test_roundtrip_05() { check_roundtrip(&generate("let_01.tfl")); }
+
+#[test]
+fn test_roundtrip_06() { check_roundtrip(&generate("unary_01.tfl")); }
+
+#[test]
+fn test_roundtrip_07() { check_roundtrip(&generate("array_01.tfl")); }
+
+#[test]
+fn test_roundtrip_08() { check_roundtrip(&generate("complex_01.tfl")); }
+
+#[test]
+fn test_roundtrip_09() { check_roundtrip(&generate("tuple_01.tfl")); }
+
+#[test]
+fn test_roundtrip_10() { check_roundtrip(&generate("tuple_02.tfl")); }
+
+#[test]
+fn test_roundtrip_11() { check_roundtrip(&generate("returns_01.tfl")); }
+
+#[test]
+fn test_roundtrip_12() { check_roundtrip(&generate_with("invoke_01.tfl",Definitions::Recursive)); }
+
+#[test]
+fn test_roundtrip_13() { check_roundtrip(&generate_with("complex_01.tfl",Definitions::Recursive)); }
+
+/// Reserved words of SMT-LIB, along with symbols defined by the
+/// theories used (or which Z3 treats as builtin), and which therefore
+/// cannot be declared.
+const BUILTINS : &[&str] = &[
+    // Reserved words of SMT-LIB 2.6
+    "BINARY", "DECIMAL", "HEXADECIMAL", "NUMERAL", "STRING",
+    "_", "!", "as", "let", "exists", "forall", "match", "par",
+    "assert", "check-sat", "check-sat-assuming", "declare-const",
+    "declare-datatype", "declare-datatypes", "declare-fun",
+    "declare-sort", "define-fun", "define-fun-rec", "define-funs-rec",
+    "define-sort", "echo", "exit", "get-assertions", "get-assignment",
+    "get-info", "get-model", "get-option", "get-proof",
+    "get-unsat-assumptions", "get-unsat-core", "get-value", "pop",
+    "push", "reset", "reset-assertions", "set-info", "set-logic",
+    "set-option",
+    // Symbols of the core, integer and sequence theories
+    "Bool", "Int", "Seq",
+    "true", "false", "not", "=>", "and", "or", "xor", "=", "distinct", "ite",
+    "-", "+", "*", "div", "mod", "abs", "<=", "<", ">=", ">",
+    "rem", "to_int", "to_real", "is_int",
+    "seq.len", "seq.nth", "seq.extract", "seq.unit", "seq.++", "seq.empty"
+];
+
+#[test]
+fn test_builtins_01() {
+    // Read back the script for every test file, and check that it
+    // declares no builtin symbols.  Note reserved_01.tfl and
+    // reserved_02.tfl use many reserved words as names.
+    let mut renamed = 0;
+    for e in fs::read_dir(REFTESTS_DIR).unwrap() {
+        let name = e.unwrap().file_name().into_string().unwrap();
+        for definitions in [Definitions::Opaque,Definitions::Recursive] {
+            let text = write(&generate_with(&name,definitions));
+            for cmd in SmtLibReader::new(&text).read().unwrap() {
+                let declared = match cmd {
+                    Command::DeclareConst(n,_)|Command::DeclareFun(n,_,_) => vec![n],
+                    Command::DefineFunsRec(defs) => defs.into_iter().map(|d| d.name).collect(),
+                    _ => Vec::new()
+                };
+                for n in declared {
+                    assert!(!BUILTINS.contains(&n.as_str()),"{name} declares builtin `{n}`");
+                    renamed += usize::from(n.split_once('@').is_some_and(|(n,_)| BUILTINS.contains(&n)));
+                }
+            }
+        }
+    }
+    // Sanity check reserved words were actually encountered
+    assert!(renamed > 0);
+}
+
+#[test]
+fn test_reader_01() {
+    let cmds = SmtLibReader::new("; comment\n(declare-const |a b| Bool)\n(check-sat)").read().unwrap();
+    assert_eq!(cmds,[Command::DeclareConst("|a b|".to_string(),Sort::Bool),Command::CheckSat]);
+}
+
+#[test]
+fn test_reader_02() {
+    let err = SmtLibReader::new("(assert (and x y)").read().unwrap_err();
+    assert_eq!(err.message,"unterminated list");
+}
+
+// ===================================================================
+// Push / Pop
+// ===================================================================
+
+#[test]
+fn test_push_pop_01() {
+    let script = generate("arith_01.tfl");
+    let checks = script.iter().filter(|c| **c == Command::CheckSat).count();
+    // One check for each assertion, subtraction and division
+    assert_eq!(checks,17);
+    // Each check is within its own scope, and requests a model
+    for (i,c) in script.iter().enumerate() {
+        if *c == Command::CheckSat {
+            assert!(matches!(script[i-1],Command::Assert(_)));
+            assert_eq!(script[i-2],Command::Push(1));
+            assert_eq!(script[i+1],Command::GetModel);
+            assert_eq!(script[i+2],Command::Pop(1));
+        }
+    }
+}
+
+// ===================================================================
+// Models
+// ===================================================================
+
+#[test]
+fn test_model_01() {
+    let r = SmtLibReader::new("((define-fun x () Int (- 1)) (define-fun b () Bool true))").read_all().unwrap();
+    let m = to_model(&r[0]).unwrap();
+    assert_eq!(m.len(),2);
+    assert_eq!(m[0].0,"x");
+    assert_eq!(m[0].1.to_string(),"(- 1)");
+    assert_eq!(m[1].1.to_string(),"true");
+}
+
+#[test]
+fn test_model_02() {
+    // Older versions of Z3 prefix the model, and include functions.
+    let r = SmtLibReader::new("(model (define-fun f ((x!0 Int)) Int 0) (define-fun y () Int 2))").read_all().unwrap();
+    let m = to_model(&r[0]).unwrap();
+    assert_eq!(m.len(),1);
+    assert_eq!(m[0].0,"y");
+}
+
+#[test]
+fn test_model_03() {
+    let r = SmtLibReader::new("(error \"model is not available\")").read_all().unwrap();
+    assert_eq!(to_model(&r[0]),None);
+}
+
+#[test]
+fn test_model_04() {
+    let value = |s: &str| to_value(&SmtLibReader::new(s).read_all().unwrap()[0]);
+    assert_eq!(value("(- 1)"),"-1");
+    assert_eq!(value("(seq.++ (seq.unit 1) (seq.unit (- 2)))"),"[1, -2]");
+    assert_eq!(value("(as seq.empty (Seq Int))"),"[]");
+    assert_eq!(value("(tuple!2 true (seq.unit 1))"),"(true, [1])");
+    // Tuple datatypes constructed by the Z3 backend are numbered
+    assert_eq!(value("(tuple!0 1 (tuple!1 2 false))"),"(1, (2, false))");
+}
+
+// ===================================================================
+// Sessions
+// ===================================================================
+
+/// Construct a session over a canned set of solver responses.  Note
+/// that the first response acknowledges `:print-success`.
+fn session(responses: &str) -> SmtSession<&[u8],Vec<u8>> {
+    SmtSession::new(responses.as_bytes(),Vec::new()).unwrap()
+}
+
+#[test]
+fn test_session_01() {
+    let mut s = session("success\nsuccess\nsuccess\nsat\n((define-fun x () Int 1))\nsuccess\n");
+    s.push(1).unwrap();
+    s.assert(Expr::VarAccess("b".to_string())).unwrap();
+    assert_eq!(s.check_sat().unwrap(),SatResult::Sat);
+    let model = s.get_model().unwrap();
+    assert_eq!(model[0].0,"x");
+    s.pop(1).unwrap();
+}
+
+#[test]
+fn test_session_02() {
+    // Responses spanning multiple lines
+    let mut s = session("success\nunsat\n(\n  (define-fun x () Int\n    (- 1))\n)\n");
+    assert_eq!(s.check_sat().unwrap(),SatResult::Unsat);
+    let model = s.get_model().unwrap();
+    assert_eq!(model[0].1.to_string(),"(- 1)");
+}
+
+#[test]
+fn test_session_03() {
+    let mut s = session("success\n(error \"line 1 column 10: unknown constant y\")\n");
+    match s.assert(Expr::VarAccess("y".to_string())) {
+        Err(SmtError::Solver(msg)) => assert_eq!(msg,"line 1 column 10: unknown constant y"),
+        _ => panic!("expected solver error")
+    }
+}
+
+#[test]
+fn test_session_04() {
+    let mut s = session("success\n");
+    assert!(matches!(s.check_sat(),Err(SmtError::Unexpected(_))));
+}
+
+// ===================================================================
+// Profiles
+// ===================================================================
+
+#[test]
+fn test_profile_01() {
+    for name in SolverProfile::NAMES {
+        let profile = SolverProfile::from_name(name).unwrap();
+        assert_eq!(profile.name(),*name);
+    }
+    assert_eq!(SolverProfile::from_name("mathsat"),None);
+}
+
+#[test]
+fn test_profile_02() {
+    assert!(SolverProfile::Z3.supports("QF_UFNIA"));
+    assert!(SolverProfile::Yices.supports("QF_UFLIA"));
+    assert!(!SolverProfile::Bitwuzla.supports("QF_LIA"));
+    assert_eq!(SolverProfile::Custom.executable(),None);
+    assert!(SolverProfile::Cvc5.supports_recursion());
+    assert!(!SolverProfile::Yices.supports_recursion());
+}
+
+#[test]
+fn test_profile_03() {
+    // Unsupported logics are rejected before the solver is started
+    let solver = SmtSolver::new(SolverProfile::Bitwuzla,Path::new("does-not-exist"));
+    let script = generate("arith_01.tfl");
+    match solver.check(&script) {
+        Err(SmtError::Unsupported(s,l)) => {
+            assert_eq!(s,"bitwuzla");
+            assert_eq!(l,"QF_LIA");
+        }
+        _ => panic!("expected unsupported logic")
+    }
+}
+
+#[test]
+fn test_profile_04() {
+    let limits = Limits{timeout: Some(100),rlimit: Some(5000)};
+    let z3 = SolverProfile::Z3.options(&limits);
+    assert!(z3.contains(&(":timeout".to_string(),"100".to_string())));
+    assert!(z3.contains(&(":rlimit".to_string(),"5000".to_string())));
+    let cvc5 = SolverProfile::Cvc5.options(&limits);
+    assert!(cvc5.contains(&(":tlimit-per".to_string(),"100".to_string())));
+    // Yices cannot enforce limits itself
+    assert_eq!(SolverProfile::Yices.options(&limits).len(),1);
+}
+
+// ===================================================================
+// Limits
+// ===================================================================
+
+#[test]
+fn test_limits_01() {
+    let mut s = session("success\nunknown\n(:reason-unknown timeout)\n");
+    // Without a solver process, the timeout is not enforced
+    assert_eq!(s.check_sat_within(Duration::from_millis(1)).unwrap(),SatResult::Unknown);
+    assert_eq!(s.get_info(":reason-unknown").unwrap().unwrap().to_string(),"timeout");
+}
+
+#[test]
+fn test_limits_02() {
+    let mut s = session("success\n(:name \"z3\")\n");
+    assert!(matches!(s.get_info(":reason-unknown"),Err(SmtError::Unexpected(_))));
+}
+
+#[test]
+fn test_limits_03() {
+    // Solvers may decline to give a reason
+    let mut s = session("success
+unsupported
+");
+    assert_eq!(s.get_info(":reason-unknown").unwrap(),None);
+}
@@ -1,6 +1,7 @@
 use std::fs;
 use std::path::PathBuf;
-use tiny_fl::{Parser,RustPrinter};
+use std::process::Command;
+use tiny_fl::{Parser,RustPrinter,TypeChecker};
 
 pub static REFTESTS_DIR: &str = "tests/files";
 
@@ -9,7 +10,8 @@ include!(concat!(env!("OUT_DIR"), "/tfltests.rs"));
 
 /// Run a specific test by loading the file out of the reference tests
 /// repository and attempting to parse it.  All reference tests should
-/// parse correctly.
+/// parse and type check correctly, and the generated Rust should
+/// compile.
 fn check(test: &str) {
     // Construct filename
     let mut path = PathBuf::from(REFTESTS_DIR);
@@ -22,16 +24,49 @@ fn check(test: &str) {
     // Check it
     let terms = match parser.parse() {
         Ok(terms) => terms,
-        Err(_) => {
-            panic!("failed parsing: {}",filename);
+        Err(e) => {
+            panic!("failed parsing: {}: {}",filename,e);
+        }
+    };
+    // Type check it
+    let typing = match TypeChecker::new(&parser.heap).check(&terms) {
+        Ok(typing) => typing,
+        Err(e) => {
+            panic!("failed type checking: {}: {}",filename,e);
         }
     };
     // Write file
-    let mut rp = RustPrinter::new(&parser.heap);
+    let mut rp = RustPrinter::new(&parser.heap,&typing);
     //
     for t in terms {
         rp.generate(t);
     }
     //
-    println!("{}",rp.done());
+    let rust = rp.done();
+    println!("{}",rust);
+    // Compile it
+    if let Err(e) = rustc(test,&rust) {
+        panic!("failed compiling: {}: {}",filename,e);
+    }
+}
+
+/// Check a generated Rust program compiles (i.e. type and borrow
+/// checks).  Since the program may have no `main`, it is compiled as
+/// a library and only its metadata is emitted.
+fn rustc(test: &str, rust: &str) -> Result<(),String> {
+    let dir = std::env::temp_dir().join(format!("tfltests-{}",std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let src = dir.join(test).with_extension("rs");
+    fs::write(&src,rust).unwrap();
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".to_string());
+    let output = Command::new(rustc)
+        .args(["--edition","2021","--crate-type","lib","--emit","metadata","-A","warnings"])
+        .arg("--out-dir").arg(&dir)
+        .arg(&src)
+        .output().unwrap();
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
 }
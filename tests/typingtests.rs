@@ -0,0 +1,104 @@
+use tiny_fl::{Parser,Term,Type,TypeChecker,TypeError};
+
+/// Parse and type check a given input, returning the type of the
+/// body of the first declared function.
+fn check(input: &str) -> Result<Type,TypeError> {
+    let mut parser = Parser::new(input);
+    let terms = parser.parse().unwrap();
+    let typing = TypeChecker::new(&parser.heap).check(&terms)?;
+    let body = match parser.heap.get(terms[0]) {
+        Term::Function(f) => f.body,
+        _ => unreachable!()
+    };
+    Ok(typing.type_of(body).clone())
+}
+
+/// Type check a given input, returning the error message produced.
+fn check_err(input: &str) -> String {
+    check(input).unwrap_err().message
+}
+
+#[test]
+fn test_int_01() {
+    assert_eq!(check("function f(int x) -> (int r) { x - 1 }"),Ok(Type::Int(true)));
+}
+
+#[test]
+fn test_int_02() {
+    assert_eq!(check("function f(uint x) -> (uint r) { x - 1 }"),Ok(Type::Int(false)));
+}
+
+#[test]
+fn test_int_03() {
+    assert_eq!(check("function f() -> (int r) { -1 }"),Ok(Type::Int(true)));
+}
+
+#[test]
+fn test_int_04() {
+    assert_eq!(check_err("function f(int x) -> (uint r) { x }"),"expected `uint`, found `int`");
+}
+
+#[test]
+fn test_subtype_01() {
+    assert!(Type::Int(false).is_subtype(&Type::Int(true)));
+    assert!(!Type::Int(true).is_subtype(&Type::Int(false)));
+    assert!(Type::Array(Box::new(Type::Bottom)).is_subtype(&Type::Array(Box::new(Type::Bool))));
+}
+
+#[test]
+fn test_error_01() {
+    assert_eq!(check_err("function f(bool b) -> (uint r) { b + 1 }"),"expected integer, found `bool`");
+}
+
+#[test]
+fn test_error_02() {
+    assert_eq!(check_err("function f() { assert g(1) }"),"unknown function `g`");
+}
+
+#[test]
+fn test_error_03() {
+    assert_eq!(check_err("function f() { assert x }"),"unknown variable `x`");
+}
+
+#[test]
+fn test_let_01() {
+    assert_eq!(check("function f(int x) -> (int r) { let y = x; let z = y - 1; z }"),Ok(Type::Int(true)));
+}
+
+#[test]
+fn test_let_02() {
+    assert_eq!(check_err("function f() -> (uint r) { let y = if true { let z = 1; z } else { 0 }; z }"),"unknown variable `z`");
+}
+
+#[test]
+fn test_array_01() {
+    let t = Type::Array(Box::new(Type::Int(false)));
+    assert_eq!(check("function f() -> (uint[] r) { [] }"),Ok(t));
+}
+
+#[test]
+fn test_tuple_01() {
+    let t = Type::Tuple(vec![Type::Bool,Type::Int(false)]);
+    assert_eq!(check("function f() -> ((bool,uint) r) { (true,1) }"),Ok(t));
+}
+
+#[test]
+fn test_tuple_02() {
+    assert_eq!(check_err("function f((int,int) p) -> (int r) { p.2 }"),"tuple index 2 out of range for tuple of size 2");
+}
+
+#[test]
+fn test_returns_01() {
+    let t = Type::Tuple(vec![Type::Int(false),Type::Bool]);
+    assert_eq!(check("function f() -> (uint x, bool y) { (1,true) }"),Ok(t));
+}
+
+#[test]
+fn test_returns_02() {
+    assert_eq!(check("function f() -> (uint r) { let (x,y) = g(); x }\nfunction g() -> (uint a, bool b) { (1,false) }"),Ok(Type::Int(false)));
+}
+
+#[test]
+fn test_returns_03() {
+    assert_eq!(check_err("function f() -> (uint r) { let (x,y) = 1; x }"),"cannot destructure `uint` into 2 variable(s)");
+}
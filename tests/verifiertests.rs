@@ -0,0 +1,261 @@
+use std::fs;
+use std::path::{Path,PathBuf};
+use tiny_fl::{Definitions,Parser,SyntacticHeap,Term,TypeChecker,Verifier,unchecked_recursion};
+use tiny_fl::circuit::{SmtLibCircuit,SmtLibWriter,SmtSolver,SolverProfile,VcKind};
+
+pub static REFTESTS_DIR: &str = "tests/files";
+
+/// Generate the verification conditions for a given test file as an
+/// SMT-LIB script.  This does not require a solver, since the script
+/// is never actually checked.
+fn generate(test: &str) -> String {
+    generate_with(test,Definitions::Opaque)
+}
+
+fn generate_with(test: &str, definitions: Definitions) -> String {
+    let (_,_,circuit) = build(test,definitions);
+    let bytes = SmtLibWriter::new(Vec::new()).write(circuit.commands()).unwrap();
+    String::from_utf8(bytes).unwrap()
+}
+
+/// Generate the verification conditions for a given test file,
+/// returning the kind of each along with the source text from which
+/// it arose.
+fn descriptors(test: &str) -> Vec<(VcKind,String)> {
+    let (input,heap,circuit) = build(test,Definitions::Opaque);
+    circuit.descriptors().iter().map(|d| {
+        let span = heap.span(d.index);
+        (d.kind,input[span.start..span.end].to_string())
+    }).collect()
+}
+
+fn build(test: &str, definitions: Definitions) -> (String,SyntacticHeap,SmtLibCircuit<'static>) {
+    let mut path = PathBuf::from(REFTESTS_DIR);
+    path.push(test);
+    let input = fs::read_to_string(path).unwrap();
+    let mut parser = Parser::new(&input);
+    let terms = parser.parse().unwrap();
+    let typing = TypeChecker::new(&parser.heap).check(&terms).unwrap();
+    let smtlib = SmtLibCircuit::new(SmtSolver::new(SolverProfile::Z3,Path::new("z3")));
+    let circuit = Verifier::new(&parser.heap,&typing,smtlib).with_definitions(definitions).to_circuit(&terms).unwrap();
+    (input,parser.heap,circuit)
+}
+
+#[test]
+fn test_invoke_01() {
+    let smt = generate("invoke_01.tfl");
+    assert!(smt.contains("(id 1)"));
+}
+
+#[test]
+fn test_div_01() {
+    let smt = generate("div_01.tfl");
+    // Division and remainder by non-zero
+    assert!(smt.contains("(not (= y 0))"));
+    // Nested invocations (where `div` and `rem` are builtin in SMT-LIB)
+    assert!(smt.contains("(declare-fun div@1 (Int Int) Int)"));
+    assert!(smt.contains("(declare-fun rem@1 (Int Int) Int)"));
+    assert!(smt.contains("(div@1 (rem@1 7 4) 1)"));
+}
+
+#[test]
+fn test_reserved_01() {
+    let smt = generate("reserved_01.tfl");
+    // Reserved words and command names of SMT-LIB are renamed
+    assert!(smt.contains("(declare-fun push@1 (Int Int) Int)"));
+    assert!(smt.contains("(declare-fun echo@1 (Int) Int)"));
+    assert!(smt.contains("(declare-const NUMERAL@1 Int)"));
+    assert!(smt.contains("(declare-const STRING@1 Int)"));
+    assert!(smt.contains("(declare-const exit@1 Int)"));
+    assert!(smt.contains("(declare-const reset@1 Int)"));
+    assert!(smt.contains("(push@1 1 2)"));
+}
+
+#[test]
+fn test_contract_01() {
+    let smt = generate("contract_01.tfl");
+    // Precondition of inner call instantiated
+    assert!(smt.contains("(< x 10)"));
+    // Precondition of outer call instantiated using inner call
+    assert!(smt.contains("(< (inc x) 10)"));
+}
+
+#[test]
+fn test_descriptors_01() {
+    let vcs = descriptors("div_01.tfl");
+    assert_eq!(vcs[0],(VcKind::DivByZero,"x / y".to_string()));
+    assert_eq!(vcs[1],(VcKind::Ensures,"r <= x".to_string()));
+    assert_eq!(vcs[2],(VcKind::DivByZero,"x % y".to_string()));
+    assert_eq!(vcs[3],(VcKind::Ensures,"r < y".to_string()));
+    assert_eq!(vcs[4],(VcKind::Requires,"div(6,3)".to_string()));
+    assert_eq!(vcs[5],(VcKind::Assert,"div(6,3) == 2".to_string()));
+}
+
+#[test]
+fn test_descriptors_02() {
+    let vcs = descriptors("arith_01.tfl");
+    assert!(vcs.iter().any(|(k,_)| *k == VcKind::Underflow));
+    assert!(vcs.iter().all(|(k,_)| *k != VcKind::Requires));
+}
+
+#[test]
+fn test_array_01() {
+    let smt = generate("array_01.tfl");
+    // Empty array refined to element type from context
+    assert!(smt.contains("(as seq.empty (Seq Int))"));
+    // Generators introduce a constrained constant
+    assert!(smt.contains("(declare-const array!0 (Seq Int))"));
+    assert!(smt.contains("(forall ((i! Int))"));
+    assert!(smt.contains("(seq.++ (seq.unit 1) (seq.unit 1))"));
+}
+
+#[test]
+fn test_complex_01() {
+    let smt = generate("complex_01.tfl");
+    assert!(smt.contains("(declare-fun find ((Seq Int) Int Int) Int)"));
+    assert!(smt.contains("(length (as seq.empty (Seq Int)))"));
+}
+
+#[test]
+fn test_bounds_01() {
+    let vcs = descriptors("complex_01.tfl");
+    assert!(vcs.contains(&(VcKind::Bounds,"items[index]".to_string())));
+    assert!(vcs.contains(&(VcKind::Bounds,"items[1..|items|]".to_string())));
+}
+
+#[test]
+fn test_bounds_02() {
+    let smt = generate("bounds_01.tfl");
+    // Lower bound required for signed index
+    assert!(smt.contains("(and (<= 0 i) (< i (seq.len xs)))"));
+    // Access guarded by short circuiting
+    assert!(smt.contains("(<= 0 i)) (and (<= 0 i) (< i (seq.len xs)))"));
+    // Signed generator length
+    assert!(smt.contains("(>= n 0)"));
+    // Slice bounds
+    assert!(smt.contains("(and (<= 0 i) (and (<= i (seq.len xs)) (<= (seq.len xs) (seq.len xs))))"));
+}
+
+#[test]
+fn test_tuple_01() {
+    let smt = generate("tuple_01.tfl");
+    // Tuples of each size declared once
+    assert_eq!(smt.matches("(declare-datatypes ((Tuple!2 2))").count(),1);
+    assert!(smt.contains("(declare-fun f ((Tuple!2 Int Int)) Int)"));
+    assert!(smt.contains("(f (tuple!2 1 2))"));
+}
+
+#[test]
+fn test_tuple_02() {
+    let smt = generate("tuple_02.tfl");
+    assert!(smt.contains("(declare-fun swap ((Tuple!2 Int Bool)) (Tuple!2 Bool Int))"));
+    assert!(smt.contains("(= (tuple!2.0 r) (tuple!2.1 p))"));
+    // Type invariant of unsigned fields
+    assert!(smt.contains("(>= (tuple!2.0 p@1) 0)"));
+}
+
+#[test]
+fn test_returns_01() {
+    let smt = generate("returns_01.tfl");
+    assert!(smt.contains("(declare-fun divmod (Int Int) (Tuple!2 Int Int))"));
+    // Returns bound to components of the body
+    let body = "(tuple!2 (ite (>= x 0) (div x y) (- (div (- x) y))) (ite (>= x 0) (mod x y) (- (mod (- x) y))))";
+    assert!(smt.contains(&format!("(= q (tuple!2.0 {body}))")));
+    assert!(smt.contains(&format!("(= r (tuple!2.1 {body}))")));
+    // Postconditions instantiated with components of the invocation
+    assert!(smt.contains("(< (tuple!2.1 (divmod 7 2)) 2)"));
+}
+
+#[test]
+fn test_definitions_01() {
+    let smt = generate_with("invoke_01.tfl",Definitions::Recursive);
+    // Declaration replaced by definition
+    assert!(!smt.contains("(declare-fun id"));
+    assert!(smt.contains("(define-fun-rec id ((x Int)) Int x)"));
+}
+
+#[test]
+fn test_definitions_02() {
+    let smt = generate_with("complex_01.tfl",Definitions::Recursive);
+    assert!(smt.contains("(define-funs-rec ((find ((items (Seq Int)) (item Int) (index Int)) Int) (length ((items (Seq Int))) Int))"));
+    assert!(smt.contains("(find items item (+ index 1))"));
+}
+
+#[test]
+fn test_definitions_03() {
+    // Unrolling `find([1,2,3],4,0)` reaches index 3 on the fourth level
+    let call = "(= (find (seq.++ (seq.unit 1) (seq.unit 2) (seq.unit 3)) 4 (+ (+ (+ 0 1) 1) 1)) (ite";
+    let smt = generate_with("complex_01.tfl",Definitions::Unroll(4));
+    assert!(smt.contains("(declare-fun find ((Seq Int) Int Int) Int)"));
+    assert!(smt.contains(call));
+    let smt = generate_with("complex_01.tfl",Definitions::Unroll(3));
+    assert!(!smt.contains(call));
+}
+
+#[test]
+fn test_signed_01() {
+    let smt = generate("signed_01.tfl");
+    // Division and remainder truncate towards zero (as in Rust)
+    assert!(smt.contains("(ite (>= x 0) (div x y) (- (div (- x) y)))"));
+    assert!(smt.contains("(ite (>= x 0) (mod x y) (- (mod (- x) y)))"));
+    assert!(smt.contains("(ite (>= (- 7) 0) (mod (- 7) 2) (- (mod (- (- 7)) 2)))"));
+    // Non-negative literal dividends need no case split
+    assert!(smt.contains("(div 7 (- 2))"));
+    assert!(smt.contains("(mod 7 (- 2))"));
+}
+
+#[test]
+fn test_block_01() {
+    let smt = generate("block_01.tfl");
+    // Only the trailing expression determines a block's value
+    assert!(!smt.contains("array!"));
+    assert!(smt.contains("(= r x)"));
+}
+
+#[test]
+fn test_void_01() {
+    let smt = generate("void_01.tfl");
+    // Functions without returns are not declared
+    assert!(!smt.contains("(declare-fun f"));
+    // Precondition still checked at each call site
+    assert!(smt.contains("(> 2 0)"));
+    assert!(smt.contains("(> 1 0)"));
+    // Postcondition assumed at each call site
+    assert!(smt.contains("(=> (and (and true true) (> y 0)) (> y 0))"));
+}
+
+#[test]
+fn test_definitions_04() {
+    // Recursion which may not terminate is never exposed to callers
+    let smt = generate_with("recursion_01.tfl",Definitions::Unroll(2));
+    assert!(!smt.contains("(= (f 0) (+ (f 0) 1))"));
+    assert!(!smt.contains("(= (g 0) (h 0))"));
+    assert!(!smt.contains("(= (k 1) (ite"));
+    assert!(smt.contains("(= (down 1) (ite (<= 1 0) 0 (+ (down (- 1 1)) 1)))"));
+    let smt = generate_with("recursion_01.tfl",Definitions::Recursive);
+    assert!(smt.contains("(declare-fun f (Int) Int)"));
+    assert!(smt.contains("(declare-fun g (Int) Int)"));
+    assert!(smt.contains("(declare-fun k (Int) Int)"));
+    assert!(smt.contains("(declare-fun m (Int Int) Int)"));
+    assert!(smt.contains("(define-funs-rec ((down ((x Int)) Int) (up ((x Int) (n Int)) Int))"));
+    assert!(smt.contains("((ite (<= x 0) 0 (+ (down (- x 1)) 1)) (ite (>= x n) x (up (+ x 1) n)))"));
+}
+
+#[test]
+fn test_recursion_01() {
+    let input = fs::read_to_string("tests/files/recursion_01.tfl").unwrap();
+    let mut parser = Parser::new(&input);
+    let terms = parser.parse().unwrap();
+    let names : Vec<String> = unchecked_recursion(&parser.heap,&terms).iter().map(|i| {
+        match parser.heap.get(*i) {
+            Term::Function(f) => f.name.clone(),
+            _ => unreachable!()
+        }
+    }).collect();
+    assert_eq!(names,vec!["f","g","h","k","m"]);
+    // Recursion in complex_01.tfl has a measure
+    let input = fs::read_to_string("tests/files/complex_01.tfl").unwrap();
+    let mut parser = Parser::new(&input);
+    let terms = parser.parse().unwrap();
+    assert!(unchecked_recursion(&parser.heap,&terms).is_empty());
+}